@@ -1,4 +1,4 @@
-use leptos_i18n_build::TranslationsInfos;
+use leptos_i18n_build::{Options, TranslationsInfos};
 use std::path::PathBuf;
 
 fn main() {
@@ -7,7 +7,8 @@ fn main() {
 
     let i18n_mod_directory = PathBuf::from(std::env::var_os("OUT_DIR").unwrap()).join("i18n");
 
-    let translations_infos = TranslationsInfos::parse(Default::default()).unwrap();
+    let translations_infos =
+        TranslationsInfos::parse(Options::default().interpolate_display(true)).unwrap();
 
     translations_infos.rerun_if_locales_changed();
 
@@ -0,0 +1,246 @@
+use crate::input::InputFormat;
+use crate::output::OutputFormat;
+use rebalax_core::csv::parse_decimal;
+use rebalax_core::types::StrategyState;
+use std::fmt;
+use strum::IntoEnumIterator;
+
+pub const USAGE: &str = "\
+Usage: rebalax [OPTIONS] <FILE>
+
+Computes the target value of each position of a portfolio file. Use - as FILE to read from stdin.
+
+Options:
+  -s, --strategy <STRATEGY>  buy, buy-sell, sell, contribute or withdraw [default: from the file, else buy]
+  -a, --amount <AMOUNT>      Amount to contribute or withdraw
+  -f, --format <FORMAT>      Input format: json, toml or csv [default: from the file extension]
+  -o, --output <OUTPUT>      Output format: table, json or csv [default: table]
+  -h, --help                 Print help
+  -V, --version              Print version
+
+Exit codes:
+  0  Success
+  1  The result could not be written
+  2  Invalid arguments
+  3  The file could not be read or parsed
+  4  The portfolio cannot be rebalanced with the given strategy";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Run(Args),
+    Help,
+    Version,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub file: String,
+    /// Strategy given on the command line, overriding the one stored in the file.
+    pub strategy: Option<StrategyState>,
+    pub format: Option<InputFormat>,
+    pub output: OutputFormat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgsError {
+    UnknownOption { option: String },
+    MissingValue { option: String },
+    InvalidValue { option: String, value: String },
+    MissingAmount { strategy: StrategyState },
+    UnexpectedAmount { strategy: StrategyState },
+    AmountWithoutStrategy,
+    MissingFile,
+    TooManyFiles,
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::UnknownOption { option } => write!(f, "unknown option {option}"),
+            ArgsError::MissingValue { option } => write!(f, "{option} needs a value"),
+            ArgsError::InvalidValue { option, value } => {
+                write!(f, "{value} is not a valid value for {option}")
+            }
+            ArgsError::MissingAmount { strategy } => {
+                write!(f, "the {strategy} strategy needs an --amount")
+            }
+            ArgsError::UnexpectedAmount { strategy } => {
+                write!(f, "the {strategy} strategy does not take an --amount")
+            }
+            ArgsError::AmountWithoutStrategy => write!(f, "--amount needs a --strategy"),
+            ArgsError::MissingFile => write!(f, "no portfolio file given"),
+            ArgsError::TooManyFiles => write!(f, "only one portfolio file can be given"),
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+/// Parses the arguments following the program name. Options may also be written as
+/// `--option=value`.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, ArgsError> {
+    let mut args = args.into_iter();
+    let mut file = None;
+    let mut strategy = None;
+    let mut amount = None;
+    let mut format = None;
+    let mut output = OutputFormat::default();
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => {
+                (option.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| ArgsError::MissingValue {
+                    option: option.clone(),
+                })
+        };
+        match option.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-s" | "--strategy" => strategy = Some(parse_value(&option, value()?, parse_strategy)?),
+            "-a" | "--amount" => {
+                amount = Some(parse_value(&option, value()?, |x| parse_decimal(x, '.'))?)
+            }
+            "-f" | "--format" => format = Some(parse_value(&option, value()?, parse_format)?),
+            "-o" | "--output" => output = parse_value(&option, value()?, parse_format)?,
+            "-" => file = Some(set_file(file, arg)?),
+            x if x.starts_with('-') => return Err(ArgsError::UnknownOption { option: arg }),
+            _ => file = Some(set_file(file, arg)?),
+        }
+    }
+    let strategy = match (strategy, amount) {
+        (Some(strategy), Some(amount)) if strategy.amount().is_some() => {
+            Some(strategy.with_amount(amount))
+        }
+        (Some(strategy), None) if strategy.amount().is_some() => {
+            return Err(ArgsError::MissingAmount { strategy });
+        }
+        (Some(strategy), Some(_)) => return Err(ArgsError::UnexpectedAmount { strategy }),
+        (None, Some(_)) => return Err(ArgsError::AmountWithoutStrategy),
+        (strategy, None) => strategy,
+    };
+    Ok(Command::Run(Args {
+        file: file.ok_or(ArgsError::MissingFile)?,
+        strategy,
+        format,
+        output,
+    }))
+}
+
+/// Finds the strategy whose name matches `value`, ignoring case and dashes, so both `buy-sell`
+/// and `BuySell` are accepted. Amounts of the returned strategy are zero.
+fn parse_strategy(value: &str) -> Option<StrategyState> {
+    let value = value.replace(['-', '_'], "").to_lowercase();
+    StrategyState::iter().find(|x| x.to_string().to_lowercase() == value)
+}
+
+fn parse_format<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.to_lowercase().parse().ok()
+}
+
+fn parse_value<T>(
+    option: &str,
+    value: String,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<T, ArgsError> {
+    parse(&value).ok_or_else(|| ArgsError::InvalidValue {
+        option: option.to_string(),
+        value,
+    })
+}
+
+fn set_file(file: Option<String>, arg: String) -> Result<String, ArgsError> {
+    match file {
+        Some(_) => Err(ArgsError::TooManyFiles),
+        None => Ok(arg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_reads_options_and_file() {
+        // Arrange
+        let sut = args(&[
+            "--strategy=contribute",
+            "-a",
+            "1000",
+            "-o",
+            "json",
+            "portfolio.toml",
+        ]);
+
+        // Act
+        let result = parse_args(sut);
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(Command::Run(Args {
+                file: "portfolio.toml".to_string(),
+                strategy: Some(StrategyState::Contribute(dec!(1000))),
+                format: None,
+                output: OutputFormat::Json,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_strategy_names_in_any_case() {
+        // Act
+        let kebab = parse_args(args(&["-s", "buy-sell", "-"]));
+        let pascal = parse_args(args(&["-s", "BuySell", "-"]));
+
+        // Assert
+        let expected = Ok(Command::Run(Args {
+            file: "-".to_string(),
+            strategy: Some(StrategyState::BuySell),
+            format: None,
+            output: OutputFormat::Table,
+        }));
+        assert_eq!(kebab, expected);
+        assert_eq!(pascal, expected);
+    }
+
+    #[test]
+    fn parse_args_rejects_invalid_arguments() {
+        // Act
+        let unknown = parse_args(args(&["--verbose", "a.json"]));
+        let missing_value = parse_args(args(&["a.json", "-s"]));
+        let invalid = parse_args(args(&["-s", "hold", "a.json"]));
+        let missing_amount = parse_args(args(&["-s", "withdraw", "a.json"]));
+        let unexpected_amount = parse_args(args(&["-s", "sell", "-a", "5", "a.json"]));
+        let amount_only = parse_args(args(&["-a", "5", "a.json"]));
+        let missing_file = parse_args(args(&["-s", "sell"]));
+        let two_files = parse_args(args(&["a.json", "b.json"]));
+
+        // Assert
+        assert!(matches!(unknown, Err(ArgsError::UnknownOption { .. })));
+        assert!(matches!(missing_value, Err(ArgsError::MissingValue { .. })));
+        assert!(matches!(invalid, Err(ArgsError::InvalidValue { .. })));
+        assert!(matches!(
+            missing_amount,
+            Err(ArgsError::MissingAmount { .. })
+        ));
+        assert!(matches!(
+            unexpected_amount,
+            Err(ArgsError::UnexpectedAmount { .. })
+        ));
+        assert_eq!(amount_only, Err(ArgsError::AmountWithoutStrategy));
+        assert_eq!(missing_file, Err(ArgsError::MissingFile));
+        assert_eq!(two_files, Err(ArgsError::TooManyFiles));
+    }
+}
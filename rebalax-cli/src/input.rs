@@ -0,0 +1,218 @@
+use rebalax_core::csv::{self, CsvLineError};
+use rebalax_core::types::{PositionsDataStore, StrategyState};
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+use strum_macros::{Display, EnumString};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum InputFormat {
+    Json,
+    Toml,
+    Csv,
+}
+
+impl InputFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        extension.to_lowercase().parse().ok()
+    }
+}
+
+/// Positions read from a portfolio file together with the strategy stored in it, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortfolioFile {
+    pub positions: PositionsDataStore,
+    pub strategy: Option<StrategyState>,
+}
+
+#[derive(Debug)]
+pub enum InputError {
+    UnknownFormat { path: String },
+    Read(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    Csv(Vec<CsvLineError>),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::UnknownFormat { path } => write!(
+                f,
+                "cannot tell the format of {path}, use --format json, toml or csv"
+            ),
+            InputError::Read(error) => write!(f, "cannot read the file: {error}"),
+            InputError::Json(error) => write!(f, "invalid JSON: {error}"),
+            InputError::Toml(error) => write!(f, "invalid TOML: {error}"),
+            InputError::Csv(errors) => {
+                let lines = errors
+                    .iter()
+                    .map(|x| format!("line {}: {}", x.line, x.error))
+                    .collect::<Vec<_>>();
+                write!(f, "invalid CSV, {}", lines.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// Layout of a single portfolio as exported by the app, where the positions are nested and any
+/// other fields like the name or snapshots are ignored.
+#[derive(Deserialize)]
+struct NestedPortfolio {
+    positions: PositionsDataStore,
+    #[serde(default)]
+    strategy: Option<StrategyState>,
+}
+
+#[derive(Deserialize)]
+struct StrategyField {
+    #[serde(default)]
+    strategy: Option<StrategyState>,
+}
+
+/// Reads the positions from `text`. JSON and TOML files may either hold the positions at the top
+/// level or nested in a `positions` table, both with an optional `strategy`. CSV files hold one
+/// position per line as imported by the app.
+pub fn parse_portfolio(text: &str, format: InputFormat) -> Result<PortfolioFile, InputError> {
+    let value = match format {
+        InputFormat::Json => serde_json::from_str(text).map_err(InputError::Json)?,
+        InputFormat::Toml => {
+            let value: toml::Value = toml::from_str(text).map_err(InputError::Toml)?;
+            serde_json::to_value(value).map_err(InputError::Json)?
+        }
+        InputFormat::Csv => return parse_csv(text),
+    };
+    from_value(value).map_err(InputError::Json)
+}
+
+fn from_value(value: serde_json::Value) -> Result<PortfolioFile, serde_json::Error> {
+    if value.get("positions").is_some() {
+        let portfolio: NestedPortfolio = serde_json::from_value(value)?;
+        return Ok(PortfolioFile {
+            positions: portfolio.positions,
+            strategy: portfolio.strategy,
+        });
+    }
+    let StrategyField { strategy } = serde_json::from_value(value.clone())?;
+    Ok(PortfolioFile {
+        positions: serde_json::from_value(value)?,
+        strategy,
+    })
+}
+
+fn parse_csv(text: &str) -> Result<PortfolioFile, InputError> {
+    let preview = csv::parse_positions(text, '.');
+    if !preview.errors.is_empty() {
+        return Err(InputError::Csv(preview.errors));
+    }
+    let mut positions = PositionsDataStore {
+        rows: vec![],
+        ..Default::default()
+    };
+    csv::apply_import(&mut positions, &preview.positions);
+    Ok(PortfolioFile {
+        positions,
+        strategy: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn parse_portfolio_reads_flat_toml_with_strategy() {
+        // Arrange
+        let text = r#"
+            strategy = { Contribute = "500" }
+
+            [[rows]]
+            name = "Stocks"
+            current_position = 7000
+            target_allocation = 0.7
+
+            [[rows]]
+            name = "Bonds"
+            current_position = "3000.50"
+            target_allocation = 0.3
+        "#;
+
+        // Act
+        let result = parse_portfolio(text, InputFormat::Toml).unwrap();
+
+        // Assert
+        assert_eq!(result.strategy, Some(StrategyState::Contribute(dec!(500))));
+        assert_eq!(result.positions.rows.len(), 2);
+        assert_eq!(result.positions.rows[0].current_position, dec!(7000));
+        assert_eq!(result.positions.rows[0].target_allocation, dec!(0.7));
+        assert_eq!(result.positions.rows[1].current_position, dec!(3000.50));
+        assert_ne!(result.positions.rows[0].id, result.positions.rows[1].id);
+    }
+
+    #[test]
+    fn parse_portfolio_reads_nested_json_portfolio() {
+        // Arrange
+        let text = r#"{
+            "name": "Retirement",
+            "strategy": "BuySell",
+            "positions": {
+                "rows": [{ "name": "Stocks", "current_position": 100, "target_allocation": 1 }]
+            }
+        }"#;
+
+        // Act
+        let result = parse_portfolio(text, InputFormat::Json).unwrap();
+
+        // Assert
+        assert_eq!(result.strategy, Some(StrategyState::BuySell));
+        assert_eq!(result.positions.rows[0].name, "Stocks");
+        assert_eq!(result.positions.rows[0].target_allocation, dec!(1));
+    }
+
+    #[test]
+    fn parse_portfolio_reads_csv_and_reports_invalid_lines() {
+        // Arrange
+        let valid = "name;current_value;target_percent\nStocks;700;70\nBonds;300;30\n";
+        let invalid = "Stocks;700;70\nBonds;abc;30\n";
+
+        // Act
+        let result = parse_portfolio(valid, InputFormat::Csv).unwrap();
+        let error = parse_portfolio(invalid, InputFormat::Csv);
+
+        // Assert
+        assert_eq!(result.strategy, None);
+        assert_eq!(result.positions.rows.len(), 2);
+        assert_eq!(result.positions.rows[1].target_allocation, dec!(0.3));
+        assert!(matches!(error, Err(InputError::Csv(errors)) if errors[0].line == 2));
+    }
+
+    #[test]
+    fn input_format_is_detected_from_extension() {
+        // Act
+        let result = [
+            "a/b.JSON",
+            "portfolio.toml",
+            "export.csv",
+            "portfolio.txt",
+            "-",
+        ]
+        .map(InputFormat::from_path);
+
+        // Assert
+        assert_eq!(
+            result,
+            [
+                Some(InputFormat::Json),
+                Some(InputFormat::Toml),
+                Some(InputFormat::Csv),
+                None,
+                None
+            ]
+        );
+    }
+}
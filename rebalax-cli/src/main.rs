@@ -0,0 +1,100 @@
+//! Command-line interface to the Rebalax rebalancing engine, for use in scripts.
+
+mod args;
+mod input;
+mod output;
+
+use crate::args::{Args, Command};
+use crate::input::{InputError, InputFormat};
+use rebalax_core::functions::get_target_assets;
+use rebalax_core::types::RebalanceError;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+/// Failure of a run, each with its own exit code so scripts can tell them apart.
+#[derive(Debug)]
+enum CliError {
+    Output(io::Error),
+    Input(InputError),
+    Rebalance(RebalanceError),
+}
+
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CliError::Output(_) => ExitCode::from(1),
+            CliError::Input(_) => ExitCode::from(3),
+            CliError::Rebalance(_) => ExitCode::from(4),
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Output(error) => write!(f, "cannot write the result: {error}"),
+            CliError::Input(error) => error.fmt(f),
+            CliError::Rebalance(error) => write!(f, "cannot rebalance: {error}"),
+        }
+    }
+}
+
+const USAGE_EXIT_CODE: u8 = 2;
+
+fn main() -> ExitCode {
+    match args::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(args)) => match run(&args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("rebalax: {error}");
+                error.exit_code()
+            }
+        },
+        Ok(Command::Help) => {
+            println!("{}", args::USAGE);
+            ExitCode::SUCCESS
+        }
+        Ok(Command::Version) => {
+            println!("rebalax {}", env!("CARGO_PKG_VERSION"));
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("rebalax: {error}\n\n{}", args::USAGE);
+            ExitCode::from(USAGE_EXIT_CODE)
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let format = args
+        .format
+        .or_else(|| InputFormat::from_path(&args.file))
+        .ok_or_else(|| {
+            CliError::Input(InputError::UnknownFormat {
+                path: args.file.clone(),
+            })
+        })?;
+    let text = read_file(&args.file)
+        .map_err(InputError::Read)
+        .map_err(CliError::Input)?;
+    let portfolio = input::parse_portfolio(&text, format).map_err(CliError::Input)?;
+    let strategy = args.strategy.or(portfolio.strategy).unwrap_or_default();
+    let targets =
+        get_target_assets(strategy, portfolio.positions.clone()).map_err(CliError::Rebalance)?;
+    let result = output::format_results(args.output, &portfolio.positions, &targets)
+        .map_err(|error| CliError::Output(error.into()))?;
+    io::stdout()
+        .lock()
+        .write_all(result.as_bytes())
+        .map_err(CliError::Output)
+}
+
+fn read_file(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        Ok(text)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
@@ -0,0 +1,299 @@
+use rebalax_core::csv;
+use rebalax_core::types::{PositionsDataStore, TargetPosition};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use strum_macros::{Display, EnumString};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// Result for a single position. Values are in the currency of the position.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionResult {
+    pub id: Uuid,
+    pub name: String,
+    pub current_value: Decimal,
+    pub target_value: Decimal,
+    pub delta: Decimal,
+    /// Share of the whole portfolio, including the allocation of the groups above the position.
+    pub target_allocation: Decimal,
+    pub units: Option<Decimal>,
+    pub currency: Option<String>,
+}
+
+/// Pairs every position with its computed target, in the order of the file.
+pub fn position_results(
+    positions: &PositionsDataStore,
+    targets: &[TargetPosition],
+) -> Vec<PositionResult> {
+    positions
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let target = targets.iter().find(|x| x.id == row.id)?;
+            Some(PositionResult {
+                id: row.id,
+                name: row.name.clone(),
+                current_value: row.current_position,
+                target_value: target.value,
+                delta: target.value - row.current_position,
+                target_allocation: positions.effective_target(row),
+                units: target.units,
+                currency: row.currency.clone(),
+            })
+        })
+        .collect()
+}
+
+pub fn format_results(
+    format: OutputFormat,
+    positions: &PositionsDataStore,
+    targets: &[TargetPosition],
+) -> Result<String, serde_json::Error> {
+    Ok(match format {
+        OutputFormat::Table => format_table(positions, &position_results(positions, targets)),
+        OutputFormat::Json => {
+            let mut json = serde_json::to_string_pretty(&position_results(positions, targets))?;
+            json.push('\n');
+            json
+        }
+        OutputFormat::Csv => csv::export_positions(positions, Some(targets), '.'),
+    })
+}
+
+/// Aligns names to the left and numbers to the right, followed by a line with the totals. Totals
+/// are in the base currency, as positions may be held in different ones.
+fn format_table(positions: &PositionsDataStore, results: &[PositionResult]) -> String {
+    let number = |value: Decimal| value.round_dp(2).normalize().to_string();
+    let header = ["Name", "Current", "Target", "Delta", "Target %"].map(String::from);
+    let rows = results
+        .iter()
+        .map(|x| {
+            [
+                x.name.clone(),
+                number(x.current_value),
+                number(x.target_value),
+                format_delta(x.delta.round_dp(2)),
+                number(x.target_allocation * dec!(100)),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let total = |value: fn(&PositionResult) -> Decimal| {
+        results
+            .iter()
+            .map(|x| positions.to_base(x.id, value(x)))
+            .sum::<Decimal>()
+    };
+    let total_label = match positions.base_currency.as_deref().map(str::trim) {
+        Some(currency) if !currency.is_empty() => format!("Total ({})", currency.to_uppercase()),
+        _ => "Total".to_string(),
+    };
+    let footer = [
+        total_label,
+        number(total(|x| x.current_value)),
+        number(total(|x| x.target_value)),
+        format_delta(total(|x| x.delta).round_dp(2)),
+        String::new(),
+    ];
+
+    let lines = [header.clone()]
+        .into_iter()
+        .chain(rows)
+        .chain([footer])
+        .collect::<Vec<_>>();
+    let widths = (0..header.len())
+        .map(|ix| {
+            lines
+                .iter()
+                .map(|x| x[ix].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let separator = widths
+        .iter()
+        .map(|x| "-".repeat(*x))
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    let mut table = String::new();
+    for (line_ix, line) in lines.iter().enumerate() {
+        if line_ix == 1 || line_ix == lines.len() - 1 {
+            table.push_str(&separator);
+            table.push('\n');
+        }
+        let fields = line
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(ix, (field, width))| {
+                if ix == 0 {
+                    format!("{field:<width$}")
+                } else {
+                    format!("{field:>width$}")
+                }
+            })
+            .collect::<Vec<_>>();
+        table.push_str(fields.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn format_delta(delta: Decimal) -> String {
+    if delta > dec!(0) {
+        format!("+{}", delta.normalize())
+    } else {
+        delta.normalize().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rebalax_core::types::{AllocationGroup, ExchangeRate, PositionInputState};
+
+    fn positions() -> (PositionsDataStore, Vec<TargetPosition>) {
+        let stocks = Uuid::now_v7();
+        let bonds = Uuid::now_v7();
+        let positions = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: stocks,
+                    name: "Stocks".to_string(),
+                    current_position: dec!(600),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: bonds,
+                    name: "Bonds".to_string(),
+                    current_position: dec!(400),
+                    target_allocation: dec!(0.3),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let targets = vec![
+            TargetPosition {
+                id: stocks,
+                value: dec!(700),
+                units: None,
+            },
+            TargetPosition {
+                id: bonds,
+                value: dec!(300),
+                units: None,
+            },
+        ];
+        (positions, targets)
+    }
+
+    #[test]
+    fn format_results_prints_aligned_table_with_totals() {
+        // Arrange
+        let (positions, targets) = positions();
+
+        // Act
+        let result = format_results(OutputFormat::Table, &positions, &targets).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            "\
+Name    Current  Target  Delta  Target %
+------  -------  ------  -----  --------
+Stocks      600     700   +100        70
+Bonds       400     300   -100        30
+------  -------  ------  -----  --------
+Total      1000    1000      0
+"
+        );
+    }
+
+    #[test]
+    fn format_results_sums_totals_in_base_currency() {
+        // Arrange
+        let (mut positions, targets) = positions();
+        positions.base_currency = Some("eur".to_string());
+        positions.exchange_rates = vec![ExchangeRate {
+            currency: "USD".to_string(),
+            rate: dec!(0.9),
+        }];
+        positions.rows[0].currency = Some("USD".to_string());
+
+        // Act
+        let result = format_results(OutputFormat::Table, &positions, &targets).unwrap();
+
+        // Assert
+        assert_eq!(
+            result
+                .lines()
+                .last()
+                .unwrap()
+                .split_whitespace()
+                .collect::<Vec<_>>(),
+            vec!["Total", "(EUR)", "940", "930", "-10"]
+        );
+    }
+
+    #[test]
+    fn format_results_prints_allocation_of_whole_portfolio_for_grouped_positions() {
+        // Arrange
+        let (mut positions, targets) = positions();
+        let equities = Uuid::now_v7();
+        positions.groups = vec![AllocationGroup {
+            id: equities,
+            name: "Equities".to_string(),
+            target_allocation: dec!(0.7),
+            parent: None,
+        }];
+        positions.rows[0].group = Some(equities);
+        positions.rows[0].target_allocation = dec!(1);
+
+        // Act
+        let result = format_results(OutputFormat::Table, &positions, &targets).unwrap();
+
+        // Assert
+        assert_eq!(
+            result.lines().nth(2).unwrap().split_whitespace().last(),
+            Some("70")
+        );
+    }
+
+    #[test]
+    fn format_results_serializes_json_rows() {
+        // Arrange
+        let (positions, targets) = positions();
+
+        // Act
+        let result = format_results(OutputFormat::Json, &positions, &targets).unwrap();
+
+        // Assert
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(json[0]["name"], "Stocks");
+        assert_eq!(json[0]["target_value"], "700");
+        assert_eq!(json[1]["delta"], "-100");
+    }
+
+    #[test]
+    fn format_results_exports_csv() {
+        // Arrange
+        let (positions, targets) = positions();
+
+        // Act
+        let result = format_results(OutputFormat::Csv, &positions, &targets).unwrap();
+
+        // Assert
+        assert_eq!(result.lines().nth(1), Some("Stocks;600;70;700;100"));
+    }
+}
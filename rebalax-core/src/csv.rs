@@ -0,0 +1,424 @@
+use crate::types::{PositionInputState, PositionsDataStore, TargetPosition};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+pub const CSV_HEADER: [&str; 5] = [
+    "name",
+    "current_value",
+    "target_percent",
+    "target_value",
+    "delta",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedPosition {
+    pub line: usize,
+    pub name: String,
+    pub current_position: Decimal,
+    pub target_allocation: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvError {
+    MissingColumns { found: usize },
+    EmptyName,
+    InvalidNumber { value: String },
+    UnterminatedQuote,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::MissingColumns { found } => {
+                write!(f, "expected at least 3 columns, found {found}")
+            }
+            CsvError::EmptyName => write!(f, "the name is empty"),
+            CsvError::InvalidNumber { value } => write!(f, "{value} is not a number"),
+            CsvError::UnterminatedQuote => write!(f, "a quoted field is not closed"),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvLineError {
+    pub line: usize,
+    pub error: CsvError,
+}
+
+/// Result of reading a CSV file before anything is applied to the portfolio.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CsvPreview {
+    pub positions: Vec<ImportedPosition>,
+    pub errors: Vec<CsvLineError>,
+}
+
+/// Writes one line per position with the name, current value, target allocation in percent,
+/// computed target value and delta. Target value and delta stay empty when no targets could be
+/// computed. Fields are separated by `;`, so `decimal_separator` may be either `.` or `,`.
+pub fn export_positions(
+    positions: &PositionsDataStore,
+    targets: Option<&[TargetPosition]>,
+    decimal_separator: char,
+) -> String {
+    let number = |value: Decimal| {
+        value
+            .round_dp(2)
+            .normalize()
+            .to_string()
+            .replace('.', &decimal_separator.to_string())
+    };
+    let mut csv = CSV_HEADER.join(";");
+    csv.push('\n');
+    for row in &positions.rows {
+        let target = targets.and_then(|targets| targets.iter().find(|x| x.id == row.id));
+        let fields = [
+            quote_field(&row.name),
+            number(row.current_position),
+            number(row.target_allocation * dec!(100)),
+            target.map(|x| number(x.value)).unwrap_or_default(),
+            target
+                .map(|x| number(x.value - row.current_position))
+                .unwrap_or_default(),
+        ];
+        csv.push_str(&fields.join(";"));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Reads the name, current value and target allocation in percent from the first three columns.
+/// Fields may be separated by `;`, tab or `,`, detected from the first line. A first line whose
+/// values are not numbers is treated as header. Any further columns are ignored. Numbers are read
+/// with the `decimal_separator` of the locale the file was written in, see [`parse_decimal`].
+pub fn parse_positions(text: &str, decimal_separator: char) -> CsvPreview {
+    let mut preview = CsvPreview::default();
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(ix, line)| (ix + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+    let Some((_, first_line)) = lines.peek() else {
+        return preview;
+    };
+    let delimiter = detect_delimiter(first_line);
+    let mut is_first = true;
+    for (line, content) in lines {
+        let result = split_line(content, delimiter)
+            .and_then(|fields| parse_fields(line, &fields, decimal_separator));
+        match result {
+            Ok(position) => preview.positions.push(position),
+            Err(CsvError::InvalidNumber { .. }) if is_first => {}
+            Err(error) => preview.errors.push(CsvLineError { line, error }),
+        }
+        is_first = false;
+    }
+    preview
+}
+
+/// Creates positions for unknown names and updates value and target allocation of existing ones.
+/// Names are matched case-insensitively. For share-based positions the units are derived from the
+/// imported value, so value and units stay consistent.
+pub fn apply_import(positions: &mut PositionsDataStore, imported: &[ImportedPosition]) {
+    for position in imported {
+        let existing = positions
+            .rows
+            .iter_mut()
+            .find(|x| x.name.trim().to_lowercase() == position.name.to_lowercase());
+        match existing {
+            Some(row) => {
+                row.current_position = position.current_position;
+                row.target_allocation = position.target_allocation;
+                if let Some(price) = row.unit_price() {
+                    row.units = Some((position.current_position / price).normalize());
+                }
+            }
+            None => positions.rows.push(PositionInputState {
+                id: Uuid::now_v7(),
+                name: position.name.clone(),
+                current_position: position.current_position,
+                target_allocation: position.target_allocation,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+/// Parses numbers written with either `.` or `,` as decimal separator. If both occur, the last
+/// one is the decimal separator and the other one groups thousands, as does a separator occurring
+/// more than once. A single separator followed by three digits like in `1.234` is ambiguous and
+/// read as `decimal_separator` of the locale the number was written in. A trailing `%` is ignored.
+pub fn parse_decimal(value: &str, decimal_separator: char) -> Option<Decimal> {
+    let value: String = value
+        .trim()
+        .trim_end_matches('%')
+        .chars()
+        .filter(|x| !x.is_whitespace() && *x != '\'')
+        .collect();
+    if value.is_empty() {
+        return None;
+    }
+    let normalized = match (value.rfind('.'), value.rfind(',')) {
+        (Some(dot), Some(comma)) if comma > dot => value.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => value.replace(',', ""),
+        (Some(ix), None) | (None, Some(ix)) => {
+            let separator = if value[ix..].starts_with('.') {
+                '.'
+            } else {
+                ','
+            };
+            let is_grouping = value.matches(separator).count() > 1
+                || (separator != decimal_separator && value.len() - ix - 1 == 3);
+            if is_grouping {
+                value.replace(separator, "")
+            } else {
+                value.replace(separator, ".")
+            }
+        }
+        (None, None) => value,
+    };
+    Decimal::from_str(&normalized).ok()
+}
+
+fn parse_fields(
+    line: usize,
+    fields: &[String],
+    decimal_separator: char,
+) -> Result<ImportedPosition, CsvError> {
+    if fields.len() < 3 {
+        return Err(CsvError::MissingColumns {
+            found: fields.len(),
+        });
+    }
+    let name = fields[0].trim();
+    if name.is_empty() {
+        return Err(CsvError::EmptyName);
+    }
+    let number = |value: &str| {
+        if value.trim().is_empty() {
+            return Ok(dec!(0));
+        }
+        parse_decimal(value, decimal_separator).ok_or_else(|| CsvError::InvalidNumber {
+            value: value.trim().to_string(),
+        })
+    };
+    Ok(ImportedPosition {
+        line,
+        name: name.to_string(),
+        current_position: number(&fields[1])?,
+        target_allocation: number(&fields[2])? / dec!(100),
+    })
+}
+
+fn detect_delimiter(line: &str) -> char {
+    [';', '\t']
+        .into_iter()
+        .find(|x| line.contains(*x))
+        .unwrap_or(',')
+}
+
+fn split_line(line: &str, delimiter: char) -> Result<Vec<String>, CsvError> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            x if x == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            x => field.push(x),
+        }
+    }
+    if in_quotes {
+        return Err(CsvError::UnterminatedQuote);
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+fn quote_field(value: &str) -> String {
+    if value.contains([';', ',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::get_target_assets;
+    use crate::types::StrategyState;
+
+    #[test]
+    fn parse_decimal_separators() {
+        // Assert
+        assert_eq!(parse_decimal("1234.5", '.'), Some(dec!(1234.5)));
+        assert_eq!(parse_decimal("1234,5", '.'), Some(dec!(1234.5)));
+        assert_eq!(parse_decimal("1.234,5", '.'), Some(dec!(1234.5)));
+        assert_eq!(parse_decimal("1,234.5", '.'), Some(dec!(1234.5)));
+        assert_eq!(parse_decimal("1.234.567", '.'), Some(dec!(1234567)));
+        assert_eq!(parse_decimal(" 12,5 % ", '.'), Some(dec!(12.5)));
+        assert_eq!(parse_decimal("abc", '.'), None);
+    }
+
+    #[test]
+    fn parse_decimal_uses_locale_for_ambiguous_separators() {
+        // Act
+        let german = ["1.234", "1,234", "1.234,56"].map(|x| parse_decimal(x, ','));
+        let english = ["1.234", "1,234", "1,234.56"].map(|x| parse_decimal(x, '.'));
+
+        // Assert
+        assert_eq!(
+            german,
+            [Some(dec!(1234)), Some(dec!(1.234)), Some(dec!(1234.56))]
+        );
+        assert_eq!(
+            english,
+            [Some(dec!(1.234)), Some(dec!(1234)), Some(dec!(1234.56))]
+        );
+    }
+
+    #[test]
+    fn parse_positions_with_header_and_errors() {
+        // Arrange
+        let text = "Name;Wert;Ziel %\nAktien;1.500,50;60\n\n;100;10\nAnleihen;abc;40\n\"Gold; Silber\";200;\nCash;5\n";
+
+        // Act
+        let preview = parse_positions(text, ',');
+
+        // Assert
+        assert_eq!(
+            preview.positions,
+            vec![
+                ImportedPosition {
+                    line: 2,
+                    name: "Aktien".to_string(),
+                    current_position: dec!(1500.50),
+                    target_allocation: dec!(0.6),
+                },
+                ImportedPosition {
+                    line: 6,
+                    name: "Gold; Silber".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0),
+                },
+            ]
+        );
+        assert_eq!(
+            preview.errors,
+            vec![
+                CsvLineError {
+                    line: 4,
+                    error: CsvError::EmptyName
+                },
+                CsvLineError {
+                    line: 5,
+                    error: CsvError::InvalidNumber {
+                        value: "abc".to_string()
+                    }
+                },
+                CsvLineError {
+                    line: 7,
+                    error: CsvError::MissingColumns { found: 2 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_positions_comma_separated_without_header() {
+        // Arrange
+        let text = "Stocks,700.5,70\n\"Bonds\",\"300,5\",30\n\"Cash,0,0";
+
+        // Act
+        let preview = parse_positions(text, '.');
+
+        // Assert
+        assert_eq!(preview.positions.len(), 2);
+        assert_eq!(preview.positions[1].current_position, dec!(300.5));
+        assert_eq!(preview.positions[1].target_allocation, dec!(0.3));
+        assert_eq!(
+            preview.errors,
+            vec![CsvLineError {
+                line: 3,
+                error: CsvError::UnterminatedQuote
+            }]
+        );
+    }
+
+    #[test]
+    fn apply_import_creates_and_updates() {
+        // Arrange
+        let mut positions = PositionsDataStore::default();
+        positions.rows[0].name = "Stocks".to_string();
+        positions.rows[1].name = "Bonds".to_string();
+        positions.rows[1].units = Some(dec!(3));
+        positions.rows[1].price = Some(dec!(100));
+        let imported = vec![
+            ImportedPosition {
+                line: 1,
+                name: "stocks".to_string(),
+                current_position: dec!(800),
+                target_allocation: dec!(0.5),
+            },
+            ImportedPosition {
+                line: 2,
+                name: "Bonds".to_string(),
+                current_position: dec!(500),
+                target_allocation: dec!(0.3),
+            },
+            ImportedPosition {
+                line: 3,
+                name: "Gold".to_string(),
+                current_position: dec!(100),
+                target_allocation: dec!(0.2),
+            },
+        ];
+
+        // Act
+        apply_import(&mut positions, &imported);
+
+        // Assert
+        assert_eq!(positions.rows.len(), 3);
+        assert_eq!(positions.rows[0].name, "Stocks");
+        assert_eq!(positions.rows[0].current_position, dec!(800));
+        assert_eq!(positions.rows[0].target_allocation, dec!(0.5));
+        assert_eq!(positions.rows[1].units, Some(dec!(5)));
+        assert_eq!(positions.rows[2].name, "Gold");
+        assert!(positions.is_valid_target_allocation());
+    }
+
+    #[test]
+    fn export_roundtrip() {
+        // Arrange
+        let mut positions = PositionsDataStore::default();
+        positions.rows[0].name = "Stocks; World".to_string();
+        positions.rows[0].current_position = dec!(650.5);
+        positions.rows[1].current_position = dec!(349.5);
+        let targets = get_target_assets(StrategyState::BuySell, positions.clone()).unwrap();
+
+        // Act
+        let csv = export_positions(&positions, Some(&targets), ',');
+        let preview = parse_positions(&csv, ',');
+
+        // Assert
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("\"Stocks; World\";650,5;70;700;49,5")
+        );
+        assert!(preview.errors.is_empty());
+        assert_eq!(preview.positions[0].name, "Stocks; World");
+        assert_eq!(preview.positions[0].current_position, dec!(650.5));
+        assert_eq!(preview.positions[1].target_allocation, dec!(0.3));
+    }
+}
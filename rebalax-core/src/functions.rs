@@ -0,0 +1,1905 @@
+use crate::types::{
+    PositionInputState, PositionsDataStore, RebalanceError, StrategyState, TargetPosition,
+};
+use rust_decimal::{Decimal, RoundingStrategy};
+use rust_decimal_macros::dec;
+use uuid::Uuid;
+
+#[derive(Clone)]
+struct UnbalancedAsset {
+    id: Uuid,
+    allocation: Decimal,
+    target_allocation: Decimal,
+    position: Decimal,
+}
+
+pub fn get_target_assets(
+    strategy: StrategyState,
+    positions_store: PositionsDataStore,
+) -> Result<Vec<TargetPosition>, RebalanceError> {
+    if let Some(error) = positions_store.validate(strategy).first() {
+        return Err(*error);
+    }
+    let currencies = positions_store.clone();
+    let positions_store = positions_store.flattened().in_base_currency();
+
+    // Fixed amounts have to be reconciled in full, so only the other strategies leave positions
+    // within their tolerance band untouched.
+    let mut kept = if positions_store.only_outside_tolerance && strategy.amount().is_none() {
+        positions_store
+            .rows
+            .iter()
+            .filter(|x| positions_store.is_within_tolerance(x.id))
+            .map(|x| x.id)
+            .collect()
+    } else {
+        vec![]
+    };
+    let has_units = positions_store
+        .rows
+        .iter()
+        .any(|x| x.unit_price().is_some());
+    let round = |target_assets| {
+        if has_units {
+            round_to_whole_units(&positions_store, target_assets)
+        } else {
+            target_assets
+        }
+    };
+    let mut target_assets = round(
+        targets_keeping(strategy, &positions_store, &kept)
+            .unwrap_or_else(|| strategy_targets(strategy, &positions_store)),
+    );
+
+    // Uneconomic trades are given up one at a time, smallest first, and the strategy is run again
+    // so their cash goes to the other positions. Rounding to whole units can create new small
+    // trades, so the trades are checked after rounding.
+    while let Some(uneconomic) = smallest_uneconomic_trade(&positions_store, &target_assets) {
+        kept.push(uneconomic);
+        let Some(targets) = targets_keeping(strategy, &positions_store, &kept) else {
+            break;
+        };
+        target_assets = round(targets);
+    }
+
+    // Everything above is computed in the base currency, trades are placed in the position's one
+    Ok(target_assets
+        .into_iter()
+        .map(|target| TargetPosition {
+            value: currencies.in_position_currency(target.id, target.value),
+            ..target
+        })
+        .collect())
+}
+
+/// Position with the smallest trade which is not worth its fees, if fees are considered at all.
+/// Trades are compared in money precision, so leftovers of the division are no trades.
+fn smallest_uneconomic_trade(
+    positions_store: &PositionsDataStore,
+    target_assets: &[TargetPosition],
+) -> Option<Uuid> {
+    if !positions_store.consider_fees {
+        return None;
+    }
+    target_assets
+        .iter()
+        .zip(positions_store.rows.iter())
+        .map(|(target, position)| {
+            let trade = (target.value - position.current_position).round_dp(2);
+            (position.id, trade)
+        })
+        .filter(|(_, trade)| !trade.is_zero())
+        .filter(|(id, trade)| !positions_store.fees_for(*id).is_economic(*trade))
+        .min_by_key(|(_, trade)| trade.abs())
+        .map(|(id, _)| id)
+}
+
+/// Runs `strategy` on all positions except the `kept` ones, which keep their current value. The
+/// cash flow of the strategy is spread over the remaining positions only. Returns `None` if they
+/// cannot take it, which is the case if none of them has a target allocation or value to
+/// rebalance against.
+fn targets_keeping(
+    strategy: StrategyState,
+    positions_store: &PositionsDataStore,
+    kept: &[Uuid],
+) -> Option<Vec<TargetPosition>> {
+    if kept.is_empty() {
+        return Some(strategy_targets(strategy, positions_store));
+    }
+    let rows = positions_store
+        .rows
+        .iter()
+        .filter(|x| !kept.contains(&x.id))
+        .cloned()
+        .collect::<Vec<_>>();
+    let allocation_sum = rows.iter().map(|x| x.target_allocation).sum::<Decimal>();
+    let position_sum = rows.iter().map(|x| x.current_position).sum::<Decimal>();
+    let targets = if rows.is_empty() && strategy.amount().is_none() {
+        vec![]
+    } else if allocation_sum.is_zero() || (position_sum.is_zero() && strategy.amount().is_none()) {
+        return None;
+    } else {
+        let remaining = PositionsDataStore {
+            rows: rows
+                .into_iter()
+                .map(|x| PositionInputState {
+                    target_allocation: x.target_allocation / allocation_sum,
+                    ..x
+                })
+                .collect(),
+            ..positions_store.clone()
+        };
+        strategy_targets(strategy, &remaining)
+    };
+    Some(
+        positions_store
+            .rows
+            .iter()
+            .map(|position| {
+                targets
+                    .iter()
+                    .find(|x| x.id == position.id)
+                    .cloned()
+                    .unwrap_or(TargetPosition {
+                        id: position.id,
+                        value: position.current_position,
+                        units: None,
+                    })
+            })
+            .collect(),
+    )
+}
+
+/// Target values of all positions according to `strategy`, before any tolerance, fees or units
+/// are taken into account.
+fn strategy_targets(
+    strategy: StrategyState,
+    positions_store: &PositionsDataStore,
+) -> Vec<TargetPosition> {
+    let position_total = positions_store
+        .rows
+        .iter()
+        .map(|x| x.current_position)
+        .sum::<Decimal>();
+
+    match strategy {
+        z @ StrategyState::Buy | z @ StrategyState::Sell => {
+            let is_buy = z == StrategyState::Buy;
+            let polarity = if is_buy { dec!(-1) } else { dec!(1) };
+
+            let assets: Vec<UnbalancedAsset> = positions_store
+                .rows
+                .iter()
+                .map(|x| UnbalancedAsset {
+                    id: x.id,
+                    allocation: x.current_position / position_total,
+                    target_allocation: x.target_allocation,
+                    position: x.current_position,
+                })
+                .collect();
+
+            let highest_deviation = assets
+                .iter()
+                .filter(|asset| asset.target_allocation != dec!(0))
+                .min_by_key(|asset| {
+                    (asset.allocation - asset.target_allocation) * polarity
+                        / asset.target_allocation
+                })
+                .cloned()
+                .unwrap();
+
+            let factor = highest_deviation.position / highest_deviation.target_allocation;
+
+            assets
+                .iter()
+                .map(|asset| TargetPosition {
+                    id: asset.id,
+                    // Buying never sells, so positions without a target allocation are kept
+                    value: if is_buy && asset.target_allocation.is_zero() {
+                        asset.position
+                    } else {
+                        asset.target_allocation * factor
+                    },
+                    units: None,
+                })
+                .collect::<Vec<TargetPosition>>()
+        }
+        StrategyState::BuySell => positions_store
+            .rows
+            .iter()
+            .map(|position| TargetPosition {
+                id: position.id,
+                value: position.target_allocation * position_total,
+                units: None,
+            })
+            .collect(),
+        StrategyState::Contribute(amount) => distribute_contribution(amount, positions_store),
+        StrategyState::Withdraw(amount) => distribute_withdrawal(amount, positions_store),
+    }
+}
+
+/// Sums up the fees of all trades needed to get from the current positions to `target_assets`.
+pub fn get_expected_fees(
+    positions_store: &PositionsDataStore,
+    target_assets: &[TargetPosition],
+) -> Decimal {
+    if !positions_store.consider_fees {
+        return dec!(0);
+    }
+    target_assets
+        .iter()
+        .zip(positions_store.rows.iter())
+        .map(|(target, position)| {
+            let trade = target.value - position.current_position;
+            positions_store
+                .fees_for(position.id)
+                .fee_for(positions_store.to_base(position.id, trade))
+        })
+        .sum()
+}
+
+/// Invests `amount` without selling anything by filling up the most underweight positions
+/// first until their ratio of value to target allocation is levelled with the next one.
+fn distribute_contribution(
+    amount: Decimal,
+    positions_store: &PositionsDataStore,
+) -> Vec<TargetPosition> {
+    let mut candidates: Vec<(Decimal, Decimal)> = positions_store
+        .rows
+        .iter()
+        .filter(|x| x.target_allocation > dec!(0))
+        .map(|x| (x.current_position, x.target_allocation))
+        .collect();
+    candidates.sort_by_key(|(position, target_allocation)| *position / *target_allocation);
+
+    let mut level = dec!(0);
+    let mut position_sum = dec!(0);
+    let mut allocation_sum = dec!(0);
+    for (ix, (position, target_allocation)) in candidates.iter().enumerate() {
+        if ix > 0 && *position / *target_allocation >= level {
+            break;
+        }
+        position_sum += position;
+        allocation_sum += target_allocation;
+        level = (amount + position_sum) / allocation_sum;
+    }
+
+    let buys = positions_store
+        .rows
+        .iter()
+        .map(|x| (x.target_allocation * level - x.current_position).max(dec!(0)))
+        .collect();
+    // Positions without a target allocation are never bought
+    let limits = positions_store
+        .rows
+        .iter()
+        .map(|x| (x.target_allocation <= dec!(0)).then_some(dec!(0)))
+        .collect::<Vec<_>>();
+    let buys = round_preserving_sum(buys, &limits, amount, amount.normalize().scale());
+
+    positions_store
+        .rows
+        .iter()
+        .zip(buys)
+        .map(|(position, buy)| TargetPosition {
+            id: position.id,
+            value: position.current_position + buy,
+            units: None,
+        })
+        .collect()
+}
+
+/// Withdraws `amount` without buying anything. Positions without a target allocation are sold
+/// first, afterwards the most overweight positions are sold down until they are levelled with the
+/// next one.
+fn distribute_withdrawal(
+    amount: Decimal,
+    positions_store: &PositionsDataStore,
+) -> Vec<TargetPosition> {
+    let unallocated_total = positions_store
+        .rows
+        .iter()
+        .filter(|x| x.target_allocation.is_zero())
+        .map(|x| x.current_position)
+        .sum::<Decimal>();
+
+    let sells = if unallocated_total >= amount {
+        positions_store
+            .rows
+            .iter()
+            .map(|x| {
+                if x.target_allocation.is_zero() {
+                    x.current_position * amount / unallocated_total
+                } else {
+                    dec!(0)
+                }
+            })
+            .collect()
+    } else {
+        let remaining_amount = amount - unallocated_total;
+        let mut candidates: Vec<(Decimal, Decimal)> = positions_store
+            .rows
+            .iter()
+            .filter(|x| x.target_allocation > dec!(0))
+            .map(|x| (x.current_position, x.target_allocation))
+            .collect();
+        candidates.sort_by_key(|(position, target_allocation)| {
+            std::cmp::Reverse(*position / *target_allocation)
+        });
+
+        let mut level = dec!(0);
+        let mut position_sum = dec!(0);
+        let mut allocation_sum = dec!(0);
+        for (ix, (position, target_allocation)) in candidates.iter().enumerate() {
+            if ix > 0 && *position / *target_allocation <= level {
+                break;
+            }
+            position_sum += position;
+            allocation_sum += target_allocation;
+            level = ((position_sum - remaining_amount) / allocation_sum).max(dec!(0));
+        }
+
+        positions_store
+            .rows
+            .iter()
+            .map(|x| (x.current_position - x.target_allocation * level).max(dec!(0)))
+            .collect()
+    };
+    // No position is sold beyond what it holds
+    let limits = positions_store
+        .rows
+        .iter()
+        .map(|x| Some(x.current_position))
+        .collect::<Vec<_>>();
+    let sells = round_preserving_sum(sells, &limits, amount, amount.normalize().scale());
+
+    positions_store
+        .rows
+        .iter()
+        .zip(sells)
+        .map(|(position, sell)| TargetPosition {
+            id: position.id,
+            value: position.current_position - sell,
+            units: None,
+        })
+        .collect()
+}
+
+/// Rounds the targets of share-based positions to whole units. The net cash flow of the exact
+/// targets is never exceeded: if rounding leaves a deficit, the units hurting the least are given
+/// up, afterwards the remaining cash buys the units reducing the deviation from target the most.
+fn round_to_whole_units(
+    positions_store: &PositionsDataStore,
+    target_assets: Vec<TargetPosition>,
+) -> Vec<TargetPosition> {
+    let rows = &positions_store.rows;
+    let budget = target_assets
+        .iter()
+        .zip(rows.iter())
+        .map(|(target, position)| target.value - position.current_position)
+        .sum::<Decimal>();
+    let mut units_delta: Vec<Option<Decimal>> = target_assets
+        .iter()
+        .zip(rows.iter())
+        .map(|(target, position)| {
+            position
+                .unit_price()
+                .map(|price| ((target.value - position.current_position) / price).trunc())
+        })
+        .collect();
+
+    let value_for =
+        |ix: usize, units_delta: &[Option<Decimal>]| match (rows[ix].unit_price(), units_delta[ix])
+        {
+            (Some(price), Some(delta)) => rows[ix].current_position + delta * price,
+            _ => target_assets[ix].value,
+        };
+    let deviation_change = |ix: usize, units_delta: &[Option<Decimal>], step: Decimal| {
+        let value = value_for(ix, units_delta);
+        let price = rows[ix].unit_price().unwrap_or_default();
+        (target_assets[ix].value - (value + step * price)).abs()
+            - (target_assets[ix].value - value).abs()
+    };
+    let mut spent = (0..rows.len())
+        .map(|ix| value_for(ix, &units_delta) - rows[ix].current_position)
+        .sum::<Decimal>();
+
+    while spent > budget {
+        let Some(ix) = (0..rows.len())
+            .filter(|ix| {
+                units_delta[*ix].is_some_and(|delta| {
+                    rows[*ix].units.unwrap_or_default() + delta - dec!(1) >= dec!(0)
+                })
+            })
+            .min_by_key(|ix| deviation_change(*ix, &units_delta, dec!(-1)))
+        else {
+            break;
+        };
+        units_delta[ix] = units_delta[ix].map(|delta| delta - dec!(1));
+        spent -= rows[ix].unit_price().unwrap_or_default();
+    }
+
+    loop {
+        let leftover = budget - spent;
+        let Some(ix) = (0..rows.len())
+            .filter(|ix| {
+                rows[*ix]
+                    .unit_price()
+                    .is_some_and(|price| price <= leftover)
+                    && deviation_change(*ix, &units_delta, dec!(1)) < dec!(0)
+            })
+            .min_by_key(|ix| deviation_change(*ix, &units_delta, dec!(1)))
+        else {
+            break;
+        };
+        units_delta[ix] = units_delta[ix].map(|delta| delta + dec!(1));
+        spent += rows[ix].unit_price().unwrap_or_default();
+    }
+
+    (0..rows.len())
+        .map(|ix| TargetPosition {
+            id: rows[ix].id,
+            value: value_for(ix, &units_delta),
+            units: units_delta[ix].map(|delta| rows[ix].units.unwrap_or_default() + delta),
+        })
+        .collect()
+}
+
+/// Rounds non-negative `values` down to `dp` decimal places and hands out the remaining units
+/// to the values with the largest remainders, so the result sums up to `total` exactly. Values
+/// never exceed their `limits`, so units they have no room for go to the next value instead.
+fn round_preserving_sum(
+    values: Vec<Decimal>,
+    limits: &[Option<Decimal>],
+    total: Decimal,
+    dp: u32,
+) -> Vec<Decimal> {
+    let unit = Decimal::new(1, dp);
+    let mut rounded: Vec<Decimal> = values
+        .iter()
+        .map(|x| x.round_dp_with_strategy(dp, RoundingStrategy::ToZero))
+        .collect();
+
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by_key(|ix| std::cmp::Reverse(values[*ix] - rounded[*ix]));
+
+    let mut remainder = total - rounded.iter().sum::<Decimal>();
+    let mut is_handed_out = true;
+    while remainder >= unit && is_handed_out {
+        is_handed_out = false;
+        for ix in &order {
+            if remainder < unit {
+                break;
+            }
+            if limits[*ix].is_some_and(|limit| rounded[*ix] + unit > limit) {
+                continue;
+            }
+            rounded[*ix] += unit;
+            remainder -= unit;
+            is_handed_out = true;
+        }
+    }
+    rounded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AllocationGroup, ExchangeRate, FeeModel, PositionInputState, PositionsDataStore,
+        StrategyState, ToleranceBand,
+    };
+
+    #[test]
+    fn get_target_assets_negative_position_returns_error() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(-300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store);
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(RebalanceError::NegativePosition { id: first_id })
+        );
+    }
+
+    #[test]
+    fn get_target_assets_buy_strategy_buys_zero_positions() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(1400));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(400));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(200));
+    }
+
+    #[test]
+    fn get_target_assets_negative_allocation_returns_error() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(1.4),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.6),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(-1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store);
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(RebalanceError::NegativeAllocation { id: third_id })
+        );
+    }
+
+    #[test]
+    fn get_target_assets_target_allocations_dont_sum_up_to_one_hundred_returns_error() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store);
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(RebalanceError::AllocationSumMismatch {
+                difference: dec!(-0.1)
+            })
+        );
+    }
+
+    #[test]
+    fn get_target_assets_target_allocations_above_one_hundred_returns_error() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store);
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(RebalanceError::AllocationSumMismatch {
+                difference: dec!(0.1)
+            })
+        );
+    }
+
+    #[test]
+    fn get_target_assets_buy_strategy_works() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(1400));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(400));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(200));
+    }
+
+    #[test]
+    fn get_target_assets_sell_strategy_works() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(700),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Sell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(700));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(200));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(100));
+    }
+
+    #[test]
+    fn get_target_assets_withdraw_strategy_sells_most_overweight_position() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(700),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Withdraw(dec!(100)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(700));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(300));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(200));
+    }
+
+    #[test]
+    fn get_target_assets_withdraw_strategy_levels_multiple_positions() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(700),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Withdraw(dec!(300)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(700));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(200));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(100));
+    }
+
+    #[test]
+    fn get_target_assets_withdraw_strategy_sells_unallocated_positions_first() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(500),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(500),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Withdraw(dec!(100)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(500));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(500));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(100));
+    }
+
+    #[test]
+    fn get_target_assets_withdraw_strategy_sells_unallocated_positions_completely() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(500),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(500),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Withdraw(dec!(400)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(400));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(400));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(0));
+    }
+
+    #[test]
+    fn get_target_assets_withdraw_strategy_never_sells_more_than_held() {
+        // Arrange
+        let position = |current_position, target_allocation| PositionInputState {
+            id: Uuid::new_v4(),
+            name: "".to_string(),
+            current_position,
+            target_allocation,
+            ..Default::default()
+        };
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                position(dec!(500), dec!(0.5)),
+                position(dec!(500), dec!(0.5)),
+                position(dec!(100.7), dec!(0)),
+                position(dec!(100.7), dec!(0)),
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let results = [dec!(300), dec!(201)].map(|amount| {
+            let strategy = StrategyState::Withdraw(amount);
+            (
+                amount,
+                get_target_assets(strategy, positions_store.clone()).unwrap(),
+            )
+        });
+
+        // Assert
+        for (amount, result) in results {
+            assert!(result.iter().all(|x| x.value >= dec!(0)));
+            assert_eq!(
+                result.iter().map(|x| x.value).sum::<Decimal>(),
+                dec!(1201.4) - amount
+            );
+        }
+    }
+
+    #[test]
+    fn get_target_assets_withdraw_strategy_exceeding_total_returns_error() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(500),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(500),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Withdraw(dec!(1300)), positions_store);
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(RebalanceError::AmountExceedsTotal {
+                amount: dec!(1300),
+                total: dec!(1200)
+            })
+        );
+    }
+
+    #[test]
+    fn get_target_assets_buy_strategy_keeps_positions_without_target_allocation() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.3),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(100),
+                    target_allocation: dec!(0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(700));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(300));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(100));
+    }
+
+    #[test]
+    fn get_target_assets_sell_strategy_sells_out_positions_without_target_allocation() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(700),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.3),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(100),
+                    target_allocation: dec!(0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Sell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(700));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(300));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(0));
+    }
+
+    #[test]
+    fn get_target_assets_sell_strategy_zero_position_returns_error() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(700),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(100),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Sell, positions_store);
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(RebalanceError::ZeroPosition { id: second_id })
+        );
+    }
+
+    #[test]
+    fn get_target_assets_buy_strategy_empty_total_returns_error() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store);
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(RebalanceError::ZeroPosition { id: first_id })
+        );
+    }
+
+    #[test]
+    fn get_target_assets_buy_sell_strategy_flattens_groups() {
+        // Arrange
+        let equities_id = Uuid::new_v4();
+        let bonds_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::new_v4(),
+                    name: "World".to_string(),
+                    current_position: dec!(500),
+                    target_allocation: dec!(0.6),
+                    group: Some(equities_id),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::new_v4(),
+                    name: "Emerging Markets".to_string(),
+                    current_position: dec!(100),
+                    target_allocation: dec!(0.4),
+                    group: Some(equities_id),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::new_v4(),
+                    name: "Bond fund".to_string(),
+                    current_position: dec!(400),
+                    target_allocation: dec!(1),
+                    group: Some(bonds_id),
+                    ..Default::default()
+                },
+            ],
+            groups: vec![
+                AllocationGroup {
+                    id: equities_id,
+                    name: "Equities".to_string(),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                AllocationGroup {
+                    id: bonds_id,
+                    name: "Bonds".to_string(),
+                    target_allocation: dec!(0.3),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::BuySell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result[0].value, dec!(420));
+        assert_eq!(result[1].value, dec!(280));
+        assert_eq!(result[2].value, dec!(300));
+    }
+
+    #[test]
+    fn get_target_assets_buy_sell_strategy_converts_currencies() {
+        // Arrange
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::new_v4(),
+                    name: "Euro fund".to_string(),
+                    current_position: dec!(1000),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::new_v4(),
+                    name: "US fund".to_string(),
+                    current_position: dec!(1000),
+                    target_allocation: dec!(0.5),
+                    currency: Some("USD".to_string()),
+                    ..Default::default()
+                },
+            ],
+            base_currency: Some("EUR".to_string()),
+            exchange_rates: vec![ExchangeRate {
+                currency: "USD".to_string(),
+                rate: dec!(0.9),
+            }],
+            consider_fees: true,
+            fees: FeeModel {
+                percentage: Some(dec!(0.01)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::BuySell, positions_store.clone()).unwrap();
+        let fees = get_expected_fees(&positions_store, &result);
+
+        // Assert
+        assert_eq!(result[0].value, dec!(950));
+        assert_eq!(result[1].value.round_dp(2), dec!(1055.56));
+        assert_eq!(fees.round_dp(2), dec!(1));
+    }
+
+    #[test]
+    fn get_target_assets_buy_sell_strategy_works() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::BuySell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(560));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(160));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(80));
+    }
+
+    #[test]
+    fn get_target_assets_contribute_strategy_fills_most_underweight_position() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Contribute(dec!(200)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(500));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(300));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(200));
+    }
+
+    #[test]
+    fn get_target_assets_contribute_strategy_levels_multiple_positions() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Contribute(dec!(1200)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(1400));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(400));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(200));
+    }
+
+    #[test]
+    fn get_target_assets_contribute_strategy_sums_up_to_amount() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.3333),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.3333),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.3334),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Contribute(dec!(100)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(33));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(33));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value, dec!(34));
+    }
+
+    #[test]
+    fn get_target_assets_only_outside_tolerance_keeps_positions_within_band() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(600),
+                    target_allocation: dec!(0.5),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(280),
+                    target_allocation: dec!(0.3),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(120),
+                    target_allocation: dec!(0.2),
+                    ..Default::default()
+                },
+            ],
+            tolerance: ToleranceBand::five_twenty_five(),
+            only_outside_tolerance: true,
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::BuySell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value.round_dp(2), dec!(514.29));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(280));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value.round_dp(2), dec!(205.71));
+        assert_eq!(result.iter().map(|x| x.value).sum::<Decimal>(), dec!(1000));
+    }
+
+    #[test]
+    fn get_target_assets_whole_units_never_exceed_contribution() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.5),
+                    units: Some(dec!(0)),
+                    price: Some(dec!(100)),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.5),
+                    units: Some(dec!(0)),
+                    price: Some(dec!(30)),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Contribute(dec!(1000)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(500));
+        assert_eq!(result[0].units, Some(dec!(5)));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(480));
+        assert_eq!(result[1].units, Some(dec!(16)));
+    }
+
+    #[test]
+    fn get_target_assets_whole_units_cover_buys_with_sells() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.5),
+                    units: Some(dec!(5)),
+                    price: Some(dec!(40)),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(10),
+                    target_allocation: dec!(0.5),
+                    units: Some(dec!(1)),
+                    price: Some(dec!(10)),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::BuySell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(80));
+        assert_eq!(result[0].units, Some(dec!(2)));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(100));
+        assert_eq!(result[1].units, Some(dec!(10)));
+    }
+
+    #[test]
+    fn get_target_assets_whole_units_spend_leftover_cash() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.5),
+                    units: Some(dec!(0)),
+                    price: Some(dec!(70)),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.5),
+                    units: Some(dec!(0)),
+                    price: Some(dec!(30)),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        let result =
+            get_target_assets(StrategyState::Contribute(dec!(1000)), positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value, dec!(490));
+        assert_eq!(result[0].units, Some(dec!(7)));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(510));
+        assert_eq!(result[1].units, Some(dec!(17)));
+    }
+
+    #[test]
+    fn get_target_assets_considering_fees_suppresses_orders_below_minimum() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.6),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.3),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    fees: FeeModel {
+                        minimum_order: Some(dec!(0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ],
+            fees: FeeModel {
+                minimum_order: Some(dec!(100)),
+                ..Default::default()
+            },
+            consider_fees: true,
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::BuySell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, first_id);
+        assert_eq!(result[0].value.round_dp(2), dec!(428.57));
+        assert_eq!(result[1].id, second_id);
+        assert_eq!(result[1].value, dec!(300));
+        assert_eq!(result[2].id, third_id);
+        assert_eq!(result[2].value.round_dp(2), dec!(71.43));
+        assert_eq!(result.iter().map(|x| x.value).sum::<Decimal>(), dec!(800));
+    }
+
+    #[test]
+    fn get_target_assets_considering_fees_ignores_leftovers_of_division() {
+        // Arrange
+        let position = |current_position, target_allocation| PositionInputState {
+            id: Uuid::new_v4(),
+            name: "".to_string(),
+            current_position,
+            target_allocation,
+            ..Default::default()
+        };
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                position(dec!(600), dec!(0.5)),
+                position(dec!(280), dec!(0.3)),
+                position(dec!(120), dec!(0.2)),
+            ],
+            tolerance: ToleranceBand::five_twenty_five(),
+            only_outside_tolerance: true,
+            fees: FeeModel {
+                flat: Some(dec!(1)),
+                ..Default::default()
+            },
+            consider_fees: true,
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::Buy, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result[0].value.round_dp(2), dec!(600));
+        assert_eq!(result[1].value, dec!(280));
+        assert_eq!(result[2].value.round_dp(2), dec!(240));
+    }
+
+    #[test]
+    fn get_target_assets_considering_fees_checks_trades_after_rounding_to_units() {
+        // Arrange
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::new_v4(),
+                    name: "".to_string(),
+                    current_position: dec!(1000),
+                    target_allocation: dec!(0.59),
+                    units: Some(dec!(10)),
+                    price: Some(dec!(100)),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::new_v4(),
+                    name: "".to_string(),
+                    current_position: dec!(1000),
+                    target_allocation: dec!(0.41),
+                    ..Default::default()
+                },
+            ],
+            fees: FeeModel {
+                minimum_order: Some(dec!(150)),
+                ..Default::default()
+            },
+            consider_fees: true,
+            ..Default::default()
+        };
+
+        // Act
+        let result = get_target_assets(StrategyState::BuySell, positions_store).unwrap();
+
+        // Assert
+        assert_eq!(result[0].value, dec!(1000));
+        assert_eq!(result[0].units, Some(dec!(10)));
+        assert_eq!(result[1].value, dec!(1000));
+    }
+
+    #[test]
+    fn get_expected_fees_sums_fees_of_all_trades() {
+        // Arrange
+        let first_id = Uuid::new_v4();
+        let second_id = Uuid::new_v4();
+        let third_id = Uuid::new_v4();
+        let positions_store = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "".to_string(),
+                    current_position: dec!(300),
+                    target_allocation: dec!(0.2),
+                    fees: FeeModel {
+                        flat: Some(dec!(0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: third_id,
+                    name: "".to_string(),
+                    current_position: dec!(200),
+                    target_allocation: dec!(0.1),
+                    ..Default::default()
+                },
+            ],
+            fees: FeeModel {
+                flat: Some(dec!(1)),
+                percentage: Some(dec!(0.01)),
+                minimum_order: None,
+            },
+            consider_fees: true,
+            ..Default::default()
+        };
+        let target_assets =
+            get_target_assets(StrategyState::BuySell, positions_store.clone()).unwrap();
+
+        // Act
+        let result = get_expected_fees(&positions_store, &target_assets);
+
+        // Assert
+        assert_eq!(result, dec!(7.20));
+    }
+}
@@ -0,0 +1,7 @@
+//! Rebalancing engine of Rebalax: the portfolio model, its validation and the strategies computing
+//! target positions. It has no UI dependencies, so it can be shared by the app, its Tauri backend
+//! and command-line tools.
+
+pub mod csv;
+pub mod functions;
+pub mod types;
@@ -0,0 +1,2010 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PositionsDataStore {
+    pub rows: Vec<PositionInputState>,
+    #[serde(default = "ToleranceBand::five_twenty_five")]
+    pub tolerance: ToleranceBand,
+    #[serde(default)]
+    pub only_outside_tolerance: bool,
+    #[serde(default)]
+    pub fees: FeeModel,
+    #[serde(default)]
+    pub consider_fees: bool,
+    #[serde(default)]
+    pub groups: Vec<AllocationGroup>,
+    /// Currency everything is computed in. Positions without a currency of their own are held in it.
+    #[serde(default)]
+    pub base_currency: Option<String>,
+    #[serde(default)]
+    pub exchange_rates: Vec<ExchangeRate>,
+}
+
+impl Default for PositionsDataStore {
+    fn default() -> Self {
+        Self {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 1".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.70),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 2".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.30),
+                    ..Default::default()
+                },
+            ],
+            tolerance: ToleranceBand::five_twenty_five(),
+            only_outside_tolerance: false,
+            fees: FeeModel::default(),
+            consider_fees: false,
+            groups: vec![],
+            base_currency: None,
+            exchange_rates: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct PositionInputState {
+    #[serde(default = "Uuid::now_v7")]
+    pub id: Uuid,
+    pub name: String,
+    pub current_position: Decimal,
+    pub target_allocation: Decimal,
+    #[serde(default)]
+    pub tolerance: ToleranceBand,
+    #[serde(default)]
+    pub units: Option<Decimal>,
+    #[serde(default)]
+    pub price: Option<Decimal>,
+    #[serde(default)]
+    pub fees: FeeModel,
+    /// Group the target allocation is relative to, or `None` for the whole portfolio.
+    #[serde(default)]
+    pub group: Option<Uuid>,
+    /// Currency of the current value and price, or `None` for the base currency.
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+impl PositionInputState {
+    pub fn unit_price(&self) -> Option<Decimal> {
+        self.price.filter(|price| *price > dec!(0))
+    }
+}
+
+/// Order positions are displayed in within their group.
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Default,
+    EnumString,
+    Display,
+    EnumIter,
+)]
+pub enum PositionSort {
+    #[default]
+    Manual,
+    Name,
+    Value,
+    Drift,
+    Target,
+}
+
+/// Value of one unit of `currency` in the base currency.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct ExchangeRate {
+    pub currency: String,
+    pub rate: Decimal,
+}
+
+/// Node of the allocation tree, such as an asset class. Its target allocation is a fraction of
+/// its parent group, or of the whole portfolio for top-level groups.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct AllocationGroup {
+    pub id: Uuid,
+    pub name: String,
+    pub target_allocation: Decimal,
+    #[serde(default)]
+    pub parent: Option<Uuid>,
+}
+
+/// Entry of the allocation tree in display order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AllocationNode {
+    Group { id: Uuid, depth: usize },
+    Position { id: Uuid, depth: usize },
+}
+
+/// Band around a target allocation in which a drift is tolerated. The absolute width is given in
+/// allocation points, the relative width as a fraction of the target allocation. If both are set
+/// the narrower one applies.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct ToleranceBand {
+    pub absolute: Option<Decimal>,
+    pub relative: Option<Decimal>,
+}
+
+impl ToleranceBand {
+    pub fn five_twenty_five() -> Self {
+        Self {
+            absolute: Some(dec!(0.05)),
+            relative: Some(dec!(0.25)),
+        }
+    }
+    pub fn or(self, default: ToleranceBand) -> Self {
+        Self {
+            absolute: self.absolute.or(default.absolute),
+            relative: self.relative.or(default.relative),
+        }
+    }
+    pub fn width_for(&self, target_allocation: Decimal) -> Option<Decimal> {
+        match (self.absolute, self.relative) {
+            (Some(absolute), Some(relative)) => Some(absolute.min(relative * target_allocation)),
+            (Some(absolute), None) => Some(absolute),
+            (None, Some(relative)) => Some(relative * target_allocation),
+            (None, None) => None,
+        }
+    }
+    pub fn contains(&self, allocation: Decimal, target_allocation: Decimal) -> bool {
+        self.width_for(target_allocation)
+            .is_some_and(|width| (allocation - target_allocation).abs() <= width)
+    }
+}
+
+/// Costs and constraints of the broker a position is traded at. The percentage fee is given as a
+/// fraction of the traded amount, all other amounts are in the base currency.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct FeeModel {
+    pub flat: Option<Decimal>,
+    pub percentage: Option<Decimal>,
+    pub minimum_order: Option<Decimal>,
+}
+
+impl FeeModel {
+    pub fn or(self, default: FeeModel) -> Self {
+        Self {
+            flat: self.flat.or(default.flat),
+            percentage: self.percentage.or(default.percentage),
+            minimum_order: self.minimum_order.or(default.minimum_order),
+        }
+    }
+    pub fn fee_for(&self, trade: Decimal) -> Decimal {
+        if trade.is_zero() {
+            dec!(0)
+        } else {
+            self.flat.unwrap_or_default() + trade.abs() * self.percentage.unwrap_or_default()
+        }
+    }
+    /// A trade is uneconomic if it falls below the minimum order amount or its fee would eat up
+    /// the whole traded amount.
+    pub fn is_economic(&self, trade: Decimal) -> bool {
+        trade.is_zero()
+            || (trade.abs() >= self.minimum_order.unwrap_or_default()
+                && self.fee_for(trade) < trade.abs())
+    }
+}
+
+impl PositionsDataStore {
+    pub fn is_valid_target_allocation(&self) -> bool {
+        self.level_sums().iter().all(|(_, sum)| *sum == dec!(1))
+            && !self
+                .rows
+                .iter()
+                .any(|row| row.target_allocation.is_sign_negative())
+            && !self
+                .groups
+                .iter()
+                .any(|group| group.target_allocation.is_sign_negative())
+    }
+    /// Sum of all positions in the base currency.
+    pub fn total(&self) -> Decimal {
+        self.rows
+            .iter()
+            .fold(dec!(0), |acc, row| acc + self.base_value(row))
+    }
+    pub fn allocation_for(&self, id: Uuid) -> Result<Decimal, RebalanceError> {
+        let position = self
+            .rows
+            .iter()
+            .find(|x| x.id == id)
+            .ok_or(RebalanceError::UnknownPosition { id })?;
+        if self.total() == dec!(0) {
+            Ok(dec!(0))
+        } else {
+            Ok(self.base_value(position) / self.total())
+        }
+    }
+    /// Collects everything preventing `strategy` from being applied to the positions, starting
+    /// with portfolio-wide problems followed by the problems of each position.
+    pub fn validate(&self, strategy: StrategyState) -> Vec<RebalanceError> {
+        if self.rows.is_empty() {
+            return vec![RebalanceError::EmptyPortfolio];
+        }
+
+        let mut errors = vec![];
+        if !self.is_valid_target_allocation() {
+            for (group, sum) in self.level_sums() {
+                if sum == dec!(1) {
+                    continue;
+                }
+                let difference = sum - dec!(1);
+                errors.push(match group {
+                    None => RebalanceError::AllocationSumMismatch { difference },
+                    Some(id) => RebalanceError::GroupAllocationSumMismatch { id, difference },
+                });
+            }
+        }
+        for group in self.groups.iter() {
+            if group.target_allocation.is_sign_negative() {
+                errors.push(RebalanceError::NegativeGroupAllocation { id: group.id });
+            }
+        }
+        match strategy.amount() {
+            Some(amount) if amount.is_sign_negative() => {
+                errors.push(RebalanceError::NegativeAmount { amount })
+            }
+            Some(amount)
+                if matches!(strategy, StrategyState::Withdraw(_)) && amount > self.total() =>
+            {
+                errors.push(RebalanceError::AmountExceedsTotal {
+                    amount,
+                    total: self.total(),
+                })
+            }
+            _ => {}
+        }
+        for row in self.rows.iter() {
+            if row.target_allocation.is_sign_negative() {
+                errors.push(RebalanceError::NegativeAllocation { id: row.id });
+            }
+            if self.rate_for(row).is_none() {
+                errors.push(RebalanceError::MissingExchangeRate { id: row.id });
+            }
+            if row.current_position.is_sign_negative() && !row.current_position.is_zero() {
+                errors.push(RebalanceError::NegativePosition { id: row.id });
+            } else if row.current_position.is_zero() && !self.can_rebalance_zero(strategy, row) {
+                errors.push(RebalanceError::ZeroPosition { id: row.id });
+            }
+        }
+        errors
+    }
+    /// Strategies without a fixed amount need a non-zero total to rebalance against. Selling can
+    /// additionally not reach a target for a position which is already empty.
+    fn can_rebalance_zero(&self, strategy: StrategyState, row: &PositionInputState) -> bool {
+        match strategy {
+            StrategyState::Contribute(_) | StrategyState::Withdraw(_) => true,
+            StrategyState::Buy | StrategyState::BuySell => !self.total().is_zero(),
+            StrategyState::Sell => !self.total().is_zero() && self.effective_target(row).is_zero(),
+        }
+    }
+    pub fn tolerance_for(&self, id: Uuid) -> ToleranceBand {
+        self.rows
+            .iter()
+            .find(|x| x.id == id)
+            .map(|x| x.tolerance.or(self.tolerance))
+            .unwrap_or(self.tolerance)
+    }
+    pub fn fees_for(&self, id: Uuid) -> FeeModel {
+        self.rows
+            .iter()
+            .find(|x| x.id == id)
+            .map(|x| x.fees.or(self.fees))
+            .unwrap_or(self.fees)
+    }
+    pub fn is_within_tolerance(&self, id: Uuid) -> bool {
+        self.rows.iter().find(|x| x.id == id).is_some_and(|x| {
+            self.allocation_for(id).is_ok_and(|allocation| {
+                self.tolerance_for(id)
+                    .contains(allocation, self.effective_target(x))
+            })
+        })
+    }
+    /// Group of a position or group, ignoring references to groups which no longer exist.
+    fn existing_group(&self, group: Option<Uuid>) -> Option<&AllocationGroup> {
+        group.and_then(|id| self.groups.iter().find(|x| x.id == id))
+    }
+    /// Product of the target allocations from `group` up to the top level, which turns a target
+    /// allocation relative to `group` into one relative to the whole portfolio.
+    pub fn group_factor(&self, group: Option<Uuid>) -> Decimal {
+        let mut factor = dec!(1);
+        let mut current = self.existing_group(group);
+        // Bounded by the number of groups, so a corrupted cycle cannot loop forever
+        for _ in 0..self.groups.len() {
+            let Some(group) = current else {
+                break;
+            };
+            factor *= group.target_allocation;
+            current = self.existing_group(group.parent);
+        }
+        factor
+    }
+    /// Target allocation of a position relative to the whole portfolio.
+    pub fn effective_target(&self, row: &PositionInputState) -> Decimal {
+        row.target_allocation * self.group_factor(row.group)
+    }
+    /// Target allocation of a group relative to the whole portfolio.
+    pub fn group_target(&self, id: Uuid) -> Decimal {
+        self.group_factor(Some(id))
+    }
+    /// Whether `group` is `ancestor` or nested somewhere below it.
+    pub fn is_within_group(&self, group: Option<Uuid>, ancestor: Uuid) -> bool {
+        let mut current = self.existing_group(group);
+        for _ in 0..self.groups.len() {
+            let Some(group) = current else {
+                break;
+            };
+            if group.id == ancestor {
+                return true;
+            }
+            current = self.existing_group(group.parent);
+        }
+        false
+    }
+    pub fn group_value(&self, id: Uuid) -> Decimal {
+        self.rows
+            .iter()
+            .filter(|row| self.is_within_group(row.group, id))
+            .map(|row| self.base_value(row))
+            .sum()
+    }
+    /// Current share of a position in its group, comparable to its target allocation.
+    pub fn relative_allocation_for(&self, id: Uuid) -> Result<Decimal, RebalanceError> {
+        let row = self
+            .rows
+            .iter()
+            .find(|x| x.id == id)
+            .ok_or(RebalanceError::UnknownPosition { id })?;
+        let Some(group) = self.existing_group(row.group) else {
+            return self.allocation_for(id);
+        };
+        let group_value = self.group_value(group.id);
+        if group_value.is_zero() {
+            Ok(dec!(0))
+        } else {
+            Ok(self.base_value(row) / group_value)
+        }
+    }
+    pub fn group_allocation(&self, id: Uuid) -> Decimal {
+        if self.total().is_zero() {
+            dec!(0)
+        } else {
+            self.group_value(id) / self.total()
+        }
+    }
+    /// Sum of the target allocations directly below the top level (`None`) and each group, which
+    /// have to add up to 100 % each.
+    pub fn level_sums(&self) -> Vec<(Option<Uuid>, Decimal)> {
+        let parent_of_row = |row: &PositionInputState| self.existing_group(row.group).map(|x| x.id);
+        let parent_of_group =
+            |group: &AllocationGroup| self.existing_group(group.parent).map(|x| x.id);
+        let sum_for = |level: Option<Uuid>| {
+            let rows = self
+                .rows
+                .iter()
+                .filter(|row| parent_of_row(row) == level)
+                .map(|row| row.target_allocation);
+            let groups = self
+                .groups
+                .iter()
+                .filter(|group| parent_of_group(group) == level)
+                .map(|group| group.target_allocation);
+            rows.chain(groups).sum::<Decimal>()
+        };
+        std::iter::once(None)
+            .chain(self.groups.iter().map(|group| Some(group.id)))
+            .map(|level| (level, sum_for(level)))
+            .collect()
+    }
+    /// Copy with the target allocations of all positions made relative to the whole portfolio and
+    /// without groups, which is what the rebalancing strategies work on.
+    pub fn flattened(&self) -> PositionsDataStore {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| PositionInputState {
+                target_allocation: self.effective_target(row),
+                group: None,
+                ..row.clone()
+            })
+            .collect();
+        PositionsDataStore {
+            rows,
+            groups: vec![],
+            ..self.clone()
+        }
+    }
+    /// Groups and positions in display order, each group followed by its subgroups and then its
+    /// positions sorted by `sort`. The contents of `collapsed` groups are left out.
+    pub fn allocation_tree(&self, sort: PositionSort, collapsed: &[Uuid]) -> Vec<AllocationNode> {
+        let mut nodes = vec![];
+        self.push_level(None, 0, &mut nodes, sort, collapsed);
+        nodes
+    }
+    fn push_level(
+        &self,
+        level: Option<Uuid>,
+        depth: usize,
+        nodes: &mut Vec<AllocationNode>,
+        sort: PositionSort,
+        collapsed: &[Uuid],
+    ) {
+        if depth > self.groups.len() {
+            return;
+        }
+        for group in self
+            .groups
+            .iter()
+            .filter(|x| self.existing_group(x.parent).map(|x| x.id) == level)
+        {
+            nodes.push(AllocationNode::Group {
+                id: group.id,
+                depth,
+            });
+            if !collapsed.contains(&group.id) {
+                self.push_level(Some(group.id), depth + 1, nodes, sort, collapsed);
+            }
+        }
+        for row in self
+            .sorted_rows(sort)
+            .into_iter()
+            .filter(|x| self.existing_group(x.group).map(|x| x.id) == level)
+        {
+            nodes.push(AllocationNode::Position { id: row.id, depth });
+        }
+    }
+    /// Rows in the order they are displayed in when sorted by `sort`.
+    pub fn sorted_rows(&self, sort: PositionSort) -> Vec<&PositionInputState> {
+        let mut rows = self.rows.iter().collect::<Vec<_>>();
+        let drift = |row: &PositionInputState| {
+            let total = self.total();
+            let allocation = if total.is_zero() {
+                dec!(0)
+            } else {
+                self.base_value(row) / total
+            };
+            (allocation - self.effective_target(row)).abs()
+        };
+        match sort {
+            PositionSort::Manual => {}
+            PositionSort::Name => rows.sort_by_key(|x| x.name.trim().to_lowercase()),
+            PositionSort::Value => rows.sort_by_key(|x| std::cmp::Reverse(self.base_value(x))),
+            PositionSort::Drift => rows.sort_by_key(|x| std::cmp::Reverse(drift(x))),
+            PositionSort::Target => {
+                rows.sort_by_key(|x| std::cmp::Reverse(self.effective_target(x)))
+            }
+        }
+        rows
+    }
+    /// Takes over the order displayed for `sort` as manual one, so positions can be moved from
+    /// there.
+    fn keep_sorted_order(&mut self, sort: PositionSort) {
+        self.rows = self.sorted_rows(sort).into_iter().cloned().collect();
+    }
+    /// Swaps a position with its previous (`up`) or next neighbour within the same group, starting
+    /// from the order displayed for `sort`. The positions are in manual order afterwards.
+    pub fn move_position(&mut self, id: Uuid, up: bool, sort: PositionSort) {
+        self.keep_sorted_order(sort);
+        let Some(ix) = self.rows.iter().position(|x| x.id == id) else {
+            return;
+        };
+        let group = self.existing_group(self.rows[ix].group).map(|x| x.id);
+        let is_sibling =
+            |row: &PositionInputState| self.existing_group(row.group).map(|x| x.id) == group;
+        let neighbour = if up {
+            self.rows[..ix].iter().rposition(is_sibling)
+        } else {
+            self.rows[ix + 1..]
+                .iter()
+                .position(is_sibling)
+                .map(|x| x + ix + 1)
+        };
+        if let Some(neighbour) = neighbour {
+            self.rows.swap(ix, neighbour);
+        }
+    }
+    /// Whether a position can be moved in front of `before`. Positions are only reordered within
+    /// their group, as joining another one would change the target allocations.
+    pub fn can_move_position_to(&self, id: Uuid, before: Uuid) -> bool {
+        let group_of = |id| self.rows.iter().find(|x| x.id == id).map(|x| x.group);
+        id != before && group_of(id).is_some_and(|group| group_of(before) == Some(group))
+    }
+    /// Moves a position in front of `before` of the same group, starting from the order displayed
+    /// for `sort`. Used for drag and drop.
+    pub fn move_position_to(&mut self, id: Uuid, before: Uuid, sort: PositionSort) {
+        if !self.can_move_position_to(id, before) {
+            return;
+        }
+        self.keep_sorted_order(sort);
+        let Some(ix) = self.rows.iter().position(|x| x.id == id) else {
+            return;
+        };
+        let row = self.rows.remove(ix);
+        let target_ix = self
+            .rows
+            .iter()
+            .position(|x| x.id == before)
+            .unwrap_or(self.rows.len());
+        self.rows.insert(target_ix, row);
+    }
+    pub fn add_group(&mut self, parent: Option<Uuid>) {
+        self.groups.push(AllocationGroup {
+            id: Uuid::now_v7(),
+            name: format!("Group {}", self.groups.len() + 1),
+            target_allocation: dec!(0),
+            parent,
+        });
+    }
+    /// Removes a group and moves its contents one level up. Their target allocations are scaled by
+    /// the group's one, so the effective targets stay the same.
+    pub fn remove_group(&mut self, id: Uuid) {
+        let Some(ix) = self.groups.iter().position(|x| x.id == id) else {
+            return;
+        };
+        let removed = self.groups.remove(ix);
+        for row in self.rows.iter_mut().filter(|x| x.group == Some(id)) {
+            row.group = removed.parent;
+            row.target_allocation *= removed.target_allocation;
+        }
+        for group in self.groups.iter_mut().filter(|x| x.parent == Some(id)) {
+            group.parent = removed.parent;
+            group.target_allocation *= removed.target_allocation;
+        }
+    }
+    /// Value of one unit of `currency` in the base currency. Positions without a currency or in
+    /// the base currency have a rate of 1, unknown and non-positive rates are missing.
+    pub fn exchange_rate(&self, currency: Option<&str>) -> Option<Decimal> {
+        let Some(currency) = currency.map(str::trim).filter(|x| !x.is_empty()) else {
+            return Some(dec!(1));
+        };
+        if self
+            .base_currency
+            .as_deref()
+            .is_some_and(|base| base.trim().eq_ignore_ascii_case(currency))
+        {
+            return Some(dec!(1));
+        }
+        self.exchange_rates
+            .iter()
+            .find(|x| x.currency.trim().eq_ignore_ascii_case(currency))
+            .map(|x| x.rate)
+            .filter(|rate| *rate > dec!(0))
+    }
+    pub fn rate_for(&self, row: &PositionInputState) -> Option<Decimal> {
+        self.exchange_rate(row.currency.as_deref())
+    }
+    /// Current value of a position in the base currency. Positions with a missing exchange rate
+    /// are counted one to one, which validation reports.
+    pub fn base_value(&self, row: &PositionInputState) -> Decimal {
+        row.current_position * self.rate_for(row).unwrap_or(dec!(1))
+    }
+    /// Converts a value of the position `id` from the base currency into the position's currency.
+    pub fn in_position_currency(&self, id: Uuid, value: Decimal) -> Decimal {
+        self.rows
+            .iter()
+            .find(|x| x.id == id)
+            .and_then(|row| self.rate_for(row))
+            .map(|rate| value / rate)
+            .unwrap_or(value)
+    }
+    /// Converts a value of the position `id` from the position's currency into the base currency.
+    pub fn to_base(&self, id: Uuid, value: Decimal) -> Decimal {
+        self.rows
+            .iter()
+            .find(|x| x.id == id)
+            .and_then(|row| self.rate_for(row))
+            .map(|rate| value * rate)
+            .unwrap_or(value)
+    }
+    /// Copy with all values and prices converted into the base currency, which is what the
+    /// rebalancing strategies work on.
+    pub fn in_base_currency(&self) -> PositionsDataStore {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let rate = self.rate_for(row).unwrap_or(dec!(1));
+                PositionInputState {
+                    current_position: row.current_position * rate,
+                    price: row.price.map(|price| price * rate),
+                    currency: None,
+                    ..row.clone()
+                }
+            })
+            .collect();
+        PositionsDataStore {
+            rows,
+            exchange_rates: vec![],
+            ..self.clone()
+        }
+    }
+    /// Copy with the `targets` computed by a strategy taken over as current positions.
+    pub fn rebalanced(&self, targets: &[TargetPosition]) -> PositionsDataStore {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| match targets.iter().find(|x| x.id == row.id) {
+                Some(target) => PositionInputState {
+                    current_position: target.value,
+                    units: target.units.or(row.units),
+                    ..row.clone()
+                },
+                None => row.clone(),
+            })
+            .collect();
+        PositionsDataStore {
+            rows,
+            ..self.clone()
+        }
+    }
+    /// Trades needed to get from the current positions to the `targets`, in each position's
+    /// currency.
+    pub fn trades_for(&self, targets: &[TargetPosition]) -> Vec<Trade> {
+        self.rows
+            .iter()
+            .filter_map(|row| {
+                let target = targets.iter().find(|x| x.id == row.id)?;
+                Some(Trade {
+                    position: row.id,
+                    name: row.name.clone(),
+                    amount: target.value - row.current_position,
+                })
+            })
+            .collect()
+    }
+    /// Copy with the executed `trades` added to the current positions. Units of priced positions
+    /// follow their new value.
+    pub fn with_trades(&self, trades: &[Trade]) -> PositionsDataStore {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let amount = trades
+                    .iter()
+                    .filter(|x| x.position == row.id)
+                    .map(|x| x.amount)
+                    .sum::<Decimal>();
+                let current_position = row.current_position + amount;
+                PositionInputState {
+                    current_position,
+                    units: match row.unit_price() {
+                        Some(price) => Some((current_position / price).normalize()),
+                        None => row.units,
+                    },
+                    ..row.clone()
+                }
+            })
+            .collect();
+        PositionsDataStore {
+            rows,
+            ..self.clone()
+        }
+    }
+    /// Actual versus effective target allocation of every position.
+    pub fn drifts(&self) -> Vec<PositionDrift> {
+        let total = self.total();
+        self.rows
+            .iter()
+            .map(|row| PositionDrift {
+                id: row.id,
+                name: row.name.clone(),
+                allocation: if total.is_zero() {
+                    dec!(0)
+                } else {
+                    self.base_value(row) / total
+                },
+                target: self.effective_target(row),
+            })
+            .collect()
+    }
+    /// Largest absolute difference between a position's actual and target allocation.
+    pub fn max_drift(&self) -> Decimal {
+        self.drifts()
+            .iter()
+            .map(|x| x.drift().abs())
+            .max()
+            .unwrap_or_default()
+    }
+    /// Value and allocation changes per position compared to an `earlier` state. Positions are
+    /// matched by id and, as they may have been re-created by an import, by name. Positions that
+    /// only exist in one state have a value of zero in the other.
+    pub fn changes_since(&self, earlier: &PositionsDataStore) -> Vec<PositionChange> {
+        let allocation = |store: &PositionsDataStore, value: Decimal| {
+            let total = store.total();
+            if total.is_zero() {
+                dec!(0)
+            } else {
+                value / total
+            }
+        };
+        let mut matched = vec![];
+        let mut changes = self
+            .rows
+            .iter()
+            .map(|row| {
+                let before = earlier.rows.iter().find(|x| x.id == row.id).or_else(|| {
+                    earlier.rows.iter().find(|x| {
+                        x.name.trim().eq_ignore_ascii_case(row.name.trim())
+                            && !self.rows.iter().any(|y| y.id == x.id)
+                    })
+                });
+                let value_before = before.map(|x| earlier.base_value(x)).unwrap_or_default();
+                if let Some(before) = before {
+                    matched.push(before.id);
+                }
+                let value_after = self.base_value(row);
+                PositionChange {
+                    name: row.name.clone(),
+                    value_before,
+                    value_after,
+                    allocation_before: allocation(earlier, value_before),
+                    allocation_after: allocation(self, value_after),
+                }
+            })
+            .collect::<Vec<_>>();
+        changes.extend(
+            earlier
+                .rows
+                .iter()
+                .filter(|x| !matched.contains(&x.id))
+                .map(|row| {
+                    let value_before = earlier.base_value(row);
+                    PositionChange {
+                        name: row.name.clone(),
+                        value_before,
+                        value_after: dec!(0),
+                        allocation_before: allocation(earlier, value_before),
+                        allocation_after: dec!(0),
+                    }
+                }),
+        );
+        changes
+    }
+    /// Currencies positions can be held in, starting with the base currency.
+    pub fn currencies(&self) -> Vec<String> {
+        self.base_currency
+            .iter()
+            .chain(self.exchange_rates.iter().map(|x| &x.currency))
+            .map(|x| x.trim().to_uppercase())
+            .filter(|x| !x.is_empty())
+            .fold(vec![], |mut acc, x| {
+                if !acc.contains(&x) {
+                    acc.push(x);
+                }
+                acc
+            })
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    EnumString,
+    Display,
+    EnumIter,
+    Hash,
+    Eq,
+    Default,
+)]
+pub enum StrategyState {
+    #[default]
+    Buy,
+    BuySell,
+    Sell,
+    Contribute(Decimal),
+    Withdraw(Decimal),
+}
+
+impl StrategyState {
+    pub fn is_same_kind(&self, other: &StrategyState) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+    pub fn amount(&self) -> Option<Decimal> {
+        match self {
+            StrategyState::Contribute(amount) | StrategyState::Withdraw(amount) => Some(*amount),
+            _ => None,
+        }
+    }
+    pub fn with_amount(self, amount: Decimal) -> Self {
+        match self {
+            StrategyState::Contribute(_) => StrategyState::Contribute(amount),
+            StrategyState::Withdraw(_) => StrategyState::Withdraw(amount),
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Portfolio {
+    pub id: Uuid,
+    pub name: String,
+    pub positions: PositionsDataStore,
+    pub strategy: StrategyState,
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+    #[serde(default)]
+    pub trades: Vec<TradeLogEntry>,
+    /// Order the positions are displayed in.
+    #[serde(default)]
+    pub sort: PositionSort,
+}
+
+impl Portfolio {
+    pub fn new(name: String) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            name,
+            positions: PositionsDataStore::default(),
+            strategy: StrategyState::default(),
+            snapshots: vec![],
+            trades: vec![],
+            sort: PositionSort::default(),
+        }
+    }
+    /// Saves a copy of the current positions, keeping the snapshots ordered by time.
+    pub fn record_snapshot(&mut self, reason: SnapshotReason, timestamp: i64) -> Uuid {
+        let snapshot = Snapshot {
+            id: Uuid::now_v7(),
+            timestamp,
+            reason,
+            positions: self.positions.clone(),
+        };
+        let id = snapshot.id;
+        let ix = self
+            .snapshots
+            .partition_point(|x| x.timestamp <= snapshot.timestamp);
+        self.snapshots.insert(ix, snapshot);
+        id
+    }
+    pub fn remove_snapshot(&mut self, id: Uuid) {
+        self.snapshots.retain(|x| x.id != id);
+    }
+    /// Adds the executed `trades` to the positions and optionally records the result as snapshot
+    /// and the trades in the log. Returns what is needed to undo it.
+    pub fn apply_rebalance(
+        &mut self,
+        trades: Vec<Trade>,
+        options: ApplyOptions,
+        timestamp: i64,
+    ) -> AppliedRebalance {
+        self.positions = self.positions.with_trades(&trades);
+        let snapshot = options
+            .snapshot
+            .then(|| self.record_snapshot(SnapshotReason::Rebalance, timestamp));
+        let log_entry = options.log.then(|| {
+            let entry = TradeLogEntry {
+                id: Uuid::now_v7(),
+                timestamp,
+                strategy: self.strategy,
+                trades: trades
+                    .iter()
+                    .filter(|x| !x.amount.is_zero())
+                    .cloned()
+                    .collect(),
+            };
+            let id = entry.id;
+            self.trades.push(entry);
+            id
+        });
+        AppliedRebalance {
+            portfolio: self.id,
+            trades,
+            snapshot,
+            log_entry,
+        }
+    }
+    /// Takes back the applied trades. Only the traded amounts are reverted, so changes made to
+    /// the positions in the meantime are kept.
+    pub fn undo_rebalance(&mut self, applied: AppliedRebalance) {
+        let reverted = applied
+            .trades
+            .into_iter()
+            .map(|x| Trade {
+                amount: -x.amount,
+                ..x
+            })
+            .collect::<Vec<_>>();
+        self.positions = self.positions.with_trades(&reverted);
+        if let Some(id) = applied.snapshot {
+            self.remove_snapshot(id);
+        }
+        self.trades.retain(|x| Some(x.id) != applied.log_entry);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SnapshotReason {
+    #[default]
+    Manual,
+    Rebalance,
+}
+
+/// Dated copy of a portfolio's positions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub id: Uuid,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: i64,
+    pub reason: SnapshotReason,
+    pub positions: PositionsDataStore,
+}
+
+/// Amount bought (positive) or sold (negative) of a position, in the position's currency.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Trade {
+    pub position: Uuid,
+    pub name: String,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TradeLogEntry {
+    pub id: Uuid,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: i64,
+    pub strategy: StrategyState,
+    pub trades: Vec<Trade>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApplyOptions {
+    pub snapshot: bool,
+    pub log: bool,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self {
+            snapshot: true,
+            log: true,
+        }
+    }
+}
+
+/// What applying a rebalance to a portfolio changed, so it can be undone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedRebalance {
+    pub portfolio: Uuid,
+    pub trades: Vec<Trade>,
+    pub snapshot: Option<Uuid>,
+    pub log_entry: Option<Uuid>,
+}
+
+/// Actual and target allocation of a position at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionDrift {
+    pub id: Uuid,
+    pub name: String,
+    pub allocation: Decimal,
+    pub target: Decimal,
+}
+
+impl PositionDrift {
+    pub fn drift(&self) -> Decimal {
+        self.allocation - self.target
+    }
+}
+
+/// Change of a single position between two states of a portfolio, in the base currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionChange {
+    pub name: String,
+    pub value_before: Decimal,
+    pub value_after: Decimal,
+    pub allocation_before: Decimal,
+    pub allocation_after: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PortfoliosState {
+    pub portfolios: Vec<Portfolio>,
+    pub active: Uuid,
+}
+
+impl Default for PortfoliosState {
+    fn default() -> Self {
+        Self::from_portfolio(Portfolio::new("Portfolio 1".to_string()))
+    }
+}
+
+impl PortfoliosState {
+    pub fn from_portfolio(portfolio: Portfolio) -> Self {
+        Self {
+            active: portfolio.id,
+            portfolios: vec![portfolio],
+        }
+    }
+    /// Wraps the positions and strategy stored before portfolios existed into the first portfolio.
+    pub fn from_legacy(
+        positions: Option<PositionsDataStore>,
+        strategy: Option<StrategyState>,
+    ) -> Self {
+        Self::from_portfolio(Portfolio {
+            positions: positions.unwrap_or_default(),
+            strategy: strategy.unwrap_or_default(),
+            ..Portfolio::new("Portfolio 1".to_string())
+        })
+    }
+    pub fn active(&self) -> &Portfolio {
+        self.portfolios
+            .iter()
+            .find(|x| x.id == self.active)
+            .unwrap_or(&self.portfolios[0])
+    }
+    pub fn portfolio_mut(&mut self, id: Uuid) -> Option<&mut Portfolio> {
+        self.portfolios.iter_mut().find(|x| x.id == id)
+    }
+    pub fn create(&mut self) {
+        let portfolio = Portfolio::new(format!("Portfolio {}", self.portfolios.len() + 1));
+        self.active = portfolio.id;
+        self.portfolios.push(portfolio);
+    }
+    pub fn duplicate(&mut self, id: Uuid) {
+        let Some(ix) = self.portfolios.iter().position(|x| x.id == id) else {
+            return;
+        };
+        let original = &self.portfolios[ix];
+        let mut positions = original.positions.clone();
+        positions
+            .rows
+            .iter_mut()
+            .for_each(|row| row.id = Uuid::now_v7());
+        let copy = Portfolio {
+            id: Uuid::now_v7(),
+            name: format!("{} (2)", original.name),
+            positions,
+            strategy: original.strategy,
+            snapshots: vec![],
+            trades: vec![],
+            sort: original.sort,
+        };
+        self.active = copy.id;
+        self.portfolios.insert(ix + 1, copy);
+    }
+    /// Appends a portfolio read from a file and activates it. It gets a new id if it was imported
+    /// before, so the existing copy is kept.
+    pub fn import(&mut self, mut portfolio: Portfolio) {
+        if self.portfolio_mut(portfolio.id).is_some() {
+            portfolio.id = Uuid::now_v7();
+        }
+        self.active = portfolio.id;
+        self.portfolios.push(portfolio);
+    }
+    /// Removes the portfolio unless it is the last one and activates its neighbour if needed.
+    pub fn remove(&mut self, id: Uuid) {
+        if self.portfolios.len() <= 1 {
+            return;
+        }
+        let Some(ix) = self.portfolios.iter().position(|x| x.id == id) else {
+            return;
+        };
+        self.portfolios.remove(ix);
+        if self.active == id {
+            self.active = self.portfolios[ix.min(self.portfolios.len() - 1)].id;
+        }
+    }
+    /// Replaces portfolios with the same id and appends all others, activating the merged state's
+    /// active portfolio.
+    pub fn merge(&mut self, other: PortfoliosState) {
+        for portfolio in other.portfolios {
+            match self.portfolio_mut(portfolio.id) {
+                Some(existing) => *existing = portfolio,
+                None => self.portfolios.push(portfolio),
+            }
+        }
+        self.active = other.active;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RebalanceError {
+    EmptyPortfolio,
+    AllocationSumMismatch { difference: Decimal },
+    GroupAllocationSumMismatch { id: Uuid, difference: Decimal },
+    NegativeGroupAllocation { id: Uuid },
+    NegativeAllocation { id: Uuid },
+    NegativePosition { id: Uuid },
+    ZeroPosition { id: Uuid },
+    UnknownPosition { id: Uuid },
+    MissingExchangeRate { id: Uuid },
+    NegativeAmount { amount: Decimal },
+    AmountExceedsTotal { amount: Decimal, total: Decimal },
+}
+
+impl RebalanceError {
+    pub fn position_id(&self) -> Option<Uuid> {
+        match self {
+            RebalanceError::NegativeAllocation { id }
+            | RebalanceError::NegativePosition { id }
+            | RebalanceError::ZeroPosition { id }
+            | RebalanceError::UnknownPosition { id }
+            | RebalanceError::MissingExchangeRate { id } => Some(*id),
+            _ => None,
+        }
+    }
+    pub fn group_id(&self) -> Option<Uuid> {
+        match self {
+            RebalanceError::GroupAllocationSumMismatch { id, .. }
+            | RebalanceError::NegativeGroupAllocation { id } => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RebalanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RebalanceError::EmptyPortfolio => write!(f, "the portfolio has no positions"),
+            RebalanceError::AllocationSumMismatch { difference } => write!(
+                f,
+                "target allocations are off from 100 % by {} %",
+                difference * dec!(100)
+            ),
+            RebalanceError::GroupAllocationSumMismatch { id, difference } => write!(
+                f,
+                "target allocations in group {} are off from 100 % by {} %",
+                id,
+                difference * dec!(100)
+            ),
+            RebalanceError::NegativeGroupAllocation { id } => {
+                write!(f, "group {} has a negative target allocation", id)
+            }
+            RebalanceError::NegativeAllocation { id } => {
+                write!(f, "position {} has a negative target allocation", id)
+            }
+            RebalanceError::NegativePosition { id } => {
+                write!(f, "position {} has a negative value", id)
+            }
+            RebalanceError::ZeroPosition { id } => write!(f, "position {} has no value", id),
+            RebalanceError::UnknownPosition { id } => write!(f, "position {} is unknown", id),
+            RebalanceError::MissingExchangeRate { id } => {
+                write!(f, "position {} has no exchange rate", id)
+            }
+            RebalanceError::NegativeAmount { amount } => {
+                write!(f, "amount {} is negative", amount)
+            }
+            RebalanceError::AmountExceedsTotal { amount, total } => {
+                write!(f, "amount {} exceeds the total of {}", amount, total)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RebalanceError {}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TargetPosition {
+    pub id: Uuid,
+    pub value: Decimal,
+    pub units: Option<Decimal>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_valid_target_allocation_true() {
+        let sut = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 1".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.70),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 2".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.28),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 3".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.0001),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 4".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.0199),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert!(sut.is_valid_target_allocation());
+    }
+
+    #[test]
+    fn is_valid_target_allocation_exceeds_100percent() {
+        let sut = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 1".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.70),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 2".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.28),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 3".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.0002),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 4".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.0199),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert!(!sut.is_valid_target_allocation());
+    }
+
+    #[test]
+    fn is_valid_target_allocation_not_100percent() {
+        let sut = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 1".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.70),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 2".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.28),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert!(!sut.is_valid_target_allocation());
+    }
+
+    #[test]
+    fn is_valid_target_allocation_negative_position() {
+        let sut = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 1".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.70),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 2".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(0.30),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: Uuid::now_v7(),
+                    name: "Position 2".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(-0.10),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert!(!sut.is_valid_target_allocation());
+    }
+
+    #[test]
+    fn tolerance_band_five_twenty_five_uses_absolute_width_for_large_targets() {
+        let sut = ToleranceBand::five_twenty_five();
+
+        assert!(sut.contains(dec!(0.54), dec!(0.50)));
+        assert!(sut.contains(dec!(0.45), dec!(0.50)));
+        assert!(!sut.contains(dec!(0.56), dec!(0.50)));
+        assert!(!sut.contains(dec!(0.44), dec!(0.50)));
+    }
+
+    #[test]
+    fn tolerance_band_five_twenty_five_uses_relative_width_for_small_targets() {
+        let sut = ToleranceBand::five_twenty_five();
+
+        assert!(sut.contains(dec!(0.12), dec!(0.10)));
+        assert!(sut.contains(dec!(0.075), dec!(0.10)));
+        assert!(!sut.contains(dec!(0.13), dec!(0.10)));
+        assert!(!sut.contains(dec!(0.07), dec!(0.10)));
+    }
+
+    #[test]
+    fn tolerance_band_without_width_contains_nothing() {
+        let sut = ToleranceBand::default();
+
+        assert!(!sut.contains(dec!(0.10), dec!(0.10)));
+    }
+
+    #[test]
+    fn tolerance_for_falls_back_to_global_default() {
+        let id = Uuid::now_v7();
+        let sut = PositionsDataStore {
+            rows: vec![PositionInputState {
+                id,
+                name: "Position 1".to_string(),
+                current_position: dec!(0),
+                target_allocation: dec!(1),
+                tolerance: ToleranceBand {
+                    absolute: Some(dec!(0.01)),
+                    relative: None,
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sut.tolerance_for(id),
+            ToleranceBand {
+                absolute: Some(dec!(0.01)),
+                relative: Some(dec!(0.25)),
+            }
+        );
+    }
+
+    #[test]
+    fn fee_model_combines_flat_and_percentage_fee() {
+        let sut = FeeModel {
+            flat: Some(dec!(1)),
+            percentage: Some(dec!(0.0025)),
+            minimum_order: None,
+        };
+
+        assert_eq!(sut.fee_for(dec!(1000)), dec!(3.5));
+        assert_eq!(sut.fee_for(dec!(-1000)), dec!(3.5));
+        assert_eq!(sut.fee_for(dec!(0)), dec!(0));
+    }
+
+    #[test]
+    fn fee_model_rejects_uneconomic_trades() {
+        let sut = FeeModel {
+            flat: Some(dec!(5)),
+            percentage: None,
+            minimum_order: Some(dec!(25)),
+        };
+
+        assert!(sut.is_economic(dec!(25)));
+        assert!(sut.is_economic(dec!(-25)));
+        assert!(!sut.is_economic(dec!(24)));
+        assert!(!FeeModel {
+            flat: Some(dec!(5)),
+            ..Default::default()
+        }
+        .is_economic(dec!(5)));
+    }
+
+    #[test]
+    fn allocation_for_unknown_id_returns_error() {
+        let sut = PositionsDataStore::default();
+        let id = Uuid::now_v7();
+
+        assert_eq!(
+            sut.allocation_for(id),
+            Err(RebalanceError::UnknownPosition { id })
+        );
+    }
+
+    #[test]
+    fn validate_empty_portfolio() {
+        let sut = PositionsDataStore {
+            rows: vec![],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sut.validate(StrategyState::Buy),
+            vec![RebalanceError::EmptyPortfolio]
+        );
+    }
+
+    #[test]
+    fn validate_reports_every_position_specific_error() {
+        let first_id = Uuid::now_v7();
+        let second_id = Uuid::now_v7();
+        let sut = PositionsDataStore {
+            rows: vec![
+                PositionInputState {
+                    id: first_id,
+                    name: "Position 1".to_string(),
+                    current_position: dec!(0),
+                    target_allocation: dec!(1.2),
+                    ..Default::default()
+                },
+                PositionInputState {
+                    id: second_id,
+                    name: "Position 2".to_string(),
+                    current_position: dec!(-100),
+                    target_allocation: dec!(-0.1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sut.validate(StrategyState::Sell),
+            vec![
+                RebalanceError::AllocationSumMismatch {
+                    difference: dec!(0.1)
+                },
+                RebalanceError::ZeroPosition { id: first_id },
+                RebalanceError::NegativeAllocation { id: second_id },
+                RebalanceError::NegativePosition { id: second_id },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_withdrawal_exceeding_total() {
+        let sut = PositionsDataStore {
+            rows: vec![PositionInputState {
+                id: Uuid::now_v7(),
+                name: "Position 1".to_string(),
+                current_position: dec!(100),
+                target_allocation: dec!(1),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sut.validate(StrategyState::Withdraw(dec!(150))),
+            vec![RebalanceError::AmountExceedsTotal {
+                amount: dec!(150),
+                total: dec!(100),
+            }]
+        );
+    }
+
+    #[test]
+    fn portfolios_from_legacy_keeps_positions_and_strategy() {
+        let positions = PositionsDataStore::default();
+
+        let sut = PortfoliosState::from_legacy(Some(positions.clone()), Some(StrategyState::Sell));
+
+        assert_eq!(sut.portfolios.len(), 1);
+        assert_eq!(sut.active, sut.portfolios[0].id);
+        assert_eq!(sut.active().positions, positions);
+        assert_eq!(sut.active().strategy, StrategyState::Sell);
+    }
+
+    #[test]
+    fn portfolios_duplicate_activates_independent_copy() {
+        let mut sut = PortfoliosState::default();
+        let original = sut.active().clone();
+
+        sut.duplicate(original.id);
+
+        assert_eq!(sut.portfolios.len(), 2);
+        assert_ne!(sut.active, original.id);
+        assert_eq!(
+            sut.active().positions.rows.len(),
+            original.positions.rows.len()
+        );
+        assert_ne!(
+            sut.active().positions.rows[0].id,
+            original.positions.rows[0].id
+        );
+    }
+
+    #[test]
+    fn portfolios_import_keeps_existing_portfolio_with_same_id() {
+        let mut sut = PortfoliosState::default();
+        let original = sut.active().clone();
+
+        sut.import(original.clone());
+
+        assert_eq!(sut.portfolios.len(), 2);
+        assert_ne!(sut.active, original.id);
+        assert_eq!(sut.active().positions, original.positions);
+    }
+
+    #[test]
+    fn portfolios_remove_keeps_last_portfolio_and_activates_neighbour() {
+        let mut sut = PortfoliosState::default();
+        let first_id = sut.active;
+        sut.create();
+        let second_id = sut.active;
+
+        sut.remove(second_id);
+        assert_eq!(sut.active, first_id);
+
+        sut.remove(first_id);
+        assert_eq!(sut.portfolios.len(), 1);
+        assert_eq!(sut.active, first_id);
+    }
+
+    #[test]
+    fn portfolios_merge_replaces_same_ids_and_appends_others() {
+        let mut sut = PortfoliosState::default();
+        let mut other = sut.clone();
+        other.portfolios[0].name = "Renamed".to_string();
+        other.create();
+
+        sut.merge(other.clone());
+
+        assert_eq!(sut.portfolios.len(), 2);
+        assert_eq!(sut.portfolios[0].name, "Renamed");
+        assert_eq!(sut.active, other.active);
+    }
+
+    fn grouped_positions() -> PositionsDataStore {
+        let equities = Uuid::now_v7();
+        let bonds = Uuid::now_v7();
+        let position = |name: &str, target_allocation, group| PositionInputState {
+            id: Uuid::now_v7(),
+            name: name.to_string(),
+            target_allocation,
+            group: Some(group),
+            ..Default::default()
+        };
+        PositionsDataStore {
+            rows: vec![
+                position("World", dec!(0.6), equities),
+                position("Emerging Markets", dec!(0.4), equities),
+                position("Bond fund", dec!(1), bonds),
+            ],
+            groups: vec![
+                AllocationGroup {
+                    id: equities,
+                    name: "Equities".to_string(),
+                    target_allocation: dec!(0.7),
+                    ..Default::default()
+                },
+                AllocationGroup {
+                    id: bonds,
+                    name: "Bonds".to_string(),
+                    target_allocation: dec!(0.3),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn groups_flatten_into_effective_targets() {
+        let sut = grouped_positions();
+
+        let flattened = sut.flattened();
+
+        assert!(sut.is_valid_target_allocation());
+        assert_eq!(flattened.rows[0].target_allocation, dec!(0.42));
+        assert_eq!(flattened.rows[1].target_allocation, dec!(0.28));
+        assert_eq!(flattened.rows[2].target_allocation, dec!(0.3));
+        assert!(flattened.groups.is_empty());
+    }
+
+    #[test]
+    fn groups_validate_each_level() {
+        let mut sut = grouped_positions();
+        sut.rows[1].target_allocation = dec!(0.3);
+        sut.groups[1].target_allocation = dec!(-0.3);
+
+        let errors = sut.validate(StrategyState::Contribute(dec!(100)));
+
+        assert_eq!(
+            errors,
+            vec![
+                RebalanceError::AllocationSumMismatch {
+                    difference: dec!(-0.6)
+                },
+                RebalanceError::GroupAllocationSumMismatch {
+                    id: sut.groups[0].id,
+                    difference: dec!(-0.1)
+                },
+                RebalanceError::NegativeGroupAllocation {
+                    id: sut.groups[1].id
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_tree_lists_contents_after_their_group_unless_collapsed() {
+        let mut sut = grouped_positions();
+        let nested = AllocationGroup {
+            id: Uuid::now_v7(),
+            name: "Nested".to_string(),
+            target_allocation: dec!(0),
+            parent: Some(sut.groups[0].id),
+        };
+        sut.groups.push(nested.clone());
+
+        let tree = sut.allocation_tree(PositionSort::Manual, &[nested.id]);
+
+        assert_eq!(
+            tree,
+            vec![
+                AllocationNode::Group {
+                    id: sut.groups[0].id,
+                    depth: 0
+                },
+                AllocationNode::Group {
+                    id: nested.id,
+                    depth: 1
+                },
+                AllocationNode::Position {
+                    id: sut.rows[0].id,
+                    depth: 1
+                },
+                AllocationNode::Position {
+                    id: sut.rows[1].id,
+                    depth: 1
+                },
+                AllocationNode::Group {
+                    id: sut.groups[1].id,
+                    depth: 0
+                },
+                AllocationNode::Position {
+                    id: sut.rows[2].id,
+                    depth: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_remove_keeps_effective_targets() {
+        let mut sut = grouped_positions();
+        let before = sut.flattened();
+
+        sut.remove_group(sut.groups[0].id);
+
+        assert_eq!(sut.groups.len(), 1);
+        assert_eq!(sut.rows[0].group, None);
+        assert!(sut.is_valid_target_allocation());
+        assert_eq!(sut.flattened(), before);
+    }
+
+    #[test]
+    fn groups_subtotals_and_allocation() {
+        let mut sut = grouped_positions();
+        sut.rows[0].current_position = dec!(500);
+        sut.rows[1].current_position = dec!(300);
+        sut.rows[2].current_position = dec!(200);
+
+        assert_eq!(sut.group_value(sut.groups[0].id), dec!(800));
+        assert_eq!(sut.relative_allocation_for(sut.rows[1].id), Ok(dec!(0.375)));
+        assert_eq!(sut.group_allocation(sut.groups[1].id), dec!(0.2));
+        assert_eq!(sut.group_target(sut.groups[1].id), dec!(0.3));
+    }
+
+    fn currency_positions() -> PositionsDataStore {
+        let position = |name: &str, current_position, currency: Option<&str>| PositionInputState {
+            id: Uuid::now_v7(),
+            name: name.to_string(),
+            current_position,
+            target_allocation: dec!(0.5),
+            currency: currency.map(str::to_string),
+            ..Default::default()
+        };
+        PositionsDataStore {
+            rows: vec![
+                position("Euro fund", dec!(1000), Some("eur")),
+                position("US fund", dec!(1000), Some("USD")),
+            ],
+            base_currency: Some("EUR".to_string()),
+            exchange_rates: vec![ExchangeRate {
+                currency: "usd".to_string(),
+                rate: dec!(0.9),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn currencies_convert_into_base_currency() {
+        let sut = currency_positions();
+
+        assert_eq!(sut.exchange_rate(None), Some(dec!(1)));
+        assert_eq!(sut.exchange_rate(Some("Eur")), Some(dec!(1)));
+        assert_eq!(sut.exchange_rate(Some("USD")), Some(dec!(0.9)));
+        assert_eq!(sut.total(), dec!(1900));
+        assert_eq!(sut.to_base(sut.rows[1].id, dec!(100)), dec!(90));
+        assert_eq!(
+            sut.in_position_currency(sut.rows[1].id, dec!(90)),
+            dec!(100)
+        );
+        assert_eq!(sut.in_base_currency().rows[1].current_position, dec!(900));
+        assert_eq!(sut.currencies(), vec!["EUR".to_string(), "USD".to_string()]);
+    }
+
+    #[test]
+    fn currencies_validate_missing_exchange_rate() {
+        let mut sut = currency_positions();
+        sut.rows[1].currency = Some("CHF".to_string());
+        sut.exchange_rates.push(ExchangeRate {
+            currency: "GBP".to_string(),
+            rate: dec!(0),
+        });
+        sut.rows[0].currency = Some("GBP".to_string());
+
+        let errors = sut.validate(StrategyState::BuySell);
+
+        assert_eq!(
+            errors,
+            vec![
+                RebalanceError::MissingExchangeRate { id: sut.rows[0].id },
+                RebalanceError::MissingExchangeRate { id: sut.rows[1].id },
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshots_are_ordered_by_time() {
+        let mut sut = Portfolio::new("Test".to_string());
+
+        let later = sut.record_snapshot(SnapshotReason::Rebalance, 2000);
+        let earlier = sut.record_snapshot(SnapshotReason::Manual, 1000);
+        sut.positions.rows[0].name = "Changed".to_string();
+        sut.record_snapshot(SnapshotReason::Manual, 3000);
+        sut.remove_snapshot(later);
+
+        assert_eq!(sut.snapshots.len(), 2);
+        assert_eq!(sut.snapshots[0].id, earlier);
+        assert_eq!(sut.snapshots[1].positions.rows[0].name, "Changed");
+    }
+
+    #[test]
+    fn changes_since_matches_positions_by_id_or_name() {
+        let position = |name: &str, current_position| PositionInputState {
+            id: Uuid::now_v7(),
+            name: name.to_string(),
+            current_position,
+            ..Default::default()
+        };
+        let earlier = PositionsDataStore {
+            rows: vec![
+                position("Stocks", dec!(600)),
+                position("Bonds", dec!(400)),
+                position("Gold", dec!(0)),
+            ],
+            ..Default::default()
+        };
+        let mut later = earlier.clone();
+        later.rows[0].current_position = dec!(900);
+        later.rows[1] = position("bonds", dec!(300));
+        later.rows.remove(2);
+        later.rows.push(position("Cash", dec!(300)));
+
+        let changes = later.changes_since(&earlier);
+
+        assert_eq!(
+            changes,
+            vec![
+                PositionChange {
+                    name: "Stocks".to_string(),
+                    value_before: dec!(600),
+                    value_after: dec!(900),
+                    allocation_before: dec!(0.6),
+                    allocation_after: dec!(0.6),
+                },
+                PositionChange {
+                    name: "bonds".to_string(),
+                    value_before: dec!(400),
+                    value_after: dec!(300),
+                    allocation_before: dec!(0.4),
+                    allocation_after: dec!(0.2),
+                },
+                PositionChange {
+                    name: "Cash".to_string(),
+                    value_before: dec!(0),
+                    value_after: dec!(300),
+                    allocation_before: dec!(0),
+                    allocation_after: dec!(0.2),
+                },
+                PositionChange {
+                    name: "Gold".to_string(),
+                    value_before: dec!(0),
+                    value_after: dec!(0),
+                    allocation_before: dec!(0),
+                    allocation_after: dec!(0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rebalanced_takes_over_targets() {
+        let mut sut = currency_positions();
+        sut.rows[1].units = Some(dec!(10));
+        let targets = vec![TargetPosition {
+            id: sut.rows[1].id,
+            value: dec!(1200),
+            units: Some(dec!(12)),
+        }];
+
+        let rebalanced = sut.rebalanced(&targets);
+
+        assert_eq!(rebalanced.rows[0], sut.rows[0]);
+        assert_eq!(rebalanced.rows[1].current_position, dec!(1200));
+        assert_eq!(rebalanced.rows[1].units, Some(dec!(12)));
+        assert_eq!(rebalanced.total(), dec!(2080));
+    }
+
+    #[test]
+    fn apply_rebalance_adds_trades_and_can_be_undone() {
+        let mut sut = Portfolio::new("Test".to_string());
+        sut.positions.rows[0].current_position = dec!(500);
+        sut.positions.rows[1].current_position = dec!(100);
+        sut.positions.rows[1].units = Some(dec!(10));
+        sut.positions.rows[1].price = Some(dec!(10));
+        let before = sut.clone();
+        let targets = vec![
+            TargetPosition {
+                id: sut.positions.rows[0].id,
+                value: dec!(420),
+                units: None,
+            },
+            TargetPosition {
+                id: sut.positions.rows[1].id,
+                value: dec!(180),
+                units: Some(dec!(18)),
+            },
+        ];
+        let mut trades = sut.positions.trades_for(&targets);
+        trades[1].amount = dec!(150);
+
+        let applied = sut.apply_rebalance(trades, ApplyOptions::default(), 1000);
+
+        assert_eq!(sut.positions.rows[0].current_position, dec!(420));
+        assert_eq!(sut.positions.rows[1].current_position, dec!(250));
+        assert_eq!(sut.positions.rows[1].units, Some(dec!(25)));
+        assert_eq!(sut.snapshots[0].positions, sut.positions);
+        assert_eq!(sut.trades[0].trades[0].amount, dec!(-80));
+
+        sut.undo_rebalance(applied);
+
+        assert_eq!(sut, before);
+    }
+
+    #[test]
+    fn undo_rebalance_keeps_changes_made_after_applying() {
+        let mut sut = Portfolio::new("Test".to_string());
+        sut.positions.rows[0].current_position = dec!(500);
+        sut.positions.rows[1].current_position = dec!(100);
+        let trades = vec![Trade {
+            position: sut.positions.rows[1].id,
+            name: sut.positions.rows[1].name.clone(),
+            amount: dec!(50),
+        }];
+        let applied = sut.apply_rebalance(trades, ApplyOptions::default(), 1000);
+        sut.positions.rows[0].name = "Stocks".to_string();
+        sut.positions.rows[1].target_allocation = dec!(0.4);
+        sut.positions.rows.push(PositionInputState::default());
+
+        sut.undo_rebalance(applied);
+
+        assert_eq!(sut.positions.rows.len(), 3);
+        assert_eq!(sut.positions.rows[0].name, "Stocks");
+        assert_eq!(sut.positions.rows[1].current_position, dec!(100));
+        assert_eq!(sut.positions.rows[1].target_allocation, dec!(0.4));
+    }
+
+    #[test]
+    fn sorted_rows_follow_sort_option() {
+        let mut sut = grouped_positions();
+        sut.rows[0].current_position = dec!(100);
+        sut.rows[1].current_position = dec!(500);
+        sut.rows[2].current_position = dec!(400);
+        let sorted_ids = |sort| {
+            sut.sorted_rows(sort)
+                .iter()
+                .map(|x| x.id)
+                .collect::<Vec<_>>()
+        };
+
+        let by_name = sut
+            .sorted_rows(PositionSort::Name)
+            .iter()
+            .map(|x| x.name.clone())
+            .collect::<Vec<_>>();
+        let by_value = sorted_ids(PositionSort::Value);
+        let by_drift = sorted_ids(PositionSort::Drift);
+
+        assert_eq!(by_name, vec!["Bond fund", "Emerging Markets", "World"]);
+        assert_eq!(
+            by_value,
+            vec![sut.rows[1].id, sut.rows[2].id, sut.rows[0].id]
+        );
+        assert_eq!(by_drift[0], sut.rows[0].id);
+    }
+
+    #[test]
+    fn move_position_swaps_with_sibling_and_keeps_sorted_order() {
+        let mut sut = grouped_positions();
+        let ids = sut.rows.iter().map(|x| x.id).collect::<Vec<_>>();
+
+        sut.move_position(ids[0], true, PositionSort::Name);
+
+        assert_eq!(
+            sut.rows.iter().map(|x| x.id).collect::<Vec<_>>(),
+            vec![ids[2], ids[0], ids[1]]
+        );
+
+        sut.move_position(ids[0], false, PositionSort::Manual);
+        sut.move_position(ids[0], false, PositionSort::Manual);
+        assert_eq!(sut.rows[2].id, ids[0]);
+    }
+
+    #[test]
+    fn move_position_to_reorders_within_group() {
+        let mut sut = grouped_positions();
+        let ids = sut.rows.iter().map(|x| x.id).collect::<Vec<_>>();
+
+        sut.move_position_to(ids[1], ids[0], PositionSort::Manual);
+
+        assert_eq!(
+            sut.rows.iter().map(|x| x.id).collect::<Vec<_>>(),
+            vec![ids[1], ids[0], ids[2]]
+        );
+        assert_eq!(sut.rows[0].group, Some(sut.groups[0].id));
+    }
+
+    #[test]
+    fn move_position_to_keeps_group_when_dropped_on_another_group() {
+        let mut sut = grouped_positions();
+        let ids = sut.rows.iter().map(|x| x.id).collect::<Vec<_>>();
+        let errors = sut.validate(StrategyState::BuySell);
+
+        sut.move_position_to(ids[2], ids[0], PositionSort::Manual);
+
+        assert!(!sut.can_move_position_to(ids[2], ids[0]));
+        assert_eq!(
+            sut.rows.iter().map(|x| x.id).collect::<Vec<_>>(),
+            vec![ids[0], ids[1], ids[2]]
+        );
+        assert_eq!(sut.rows[2].group, Some(sut.groups[1].id));
+        assert_eq!(sut.validate(StrategyState::BuySell), errors);
+    }
+}
@@ -1,8 +1,176 @@
+mod storage;
+
+use rebalax_core::functions;
+use rebalax_core::types::{
+    Portfolio, PositionsDataStore, RebalanceError, StrategyState, TargetPosition,
+};
+use std::io::Write;
+use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_fs::{FsExt, OpenOptions};
+
+/// Asks for a location with the native save dialog and writes `contents` there. Returns `false`
+/// if the dialog was cancelled.
+#[tauri::command]
+async fn save_text_file(
+    app: AppHandle,
+    file_name: String,
+    extension: String,
+    contents: String,
+) -> Result<bool, String> {
+    run_blocking(move || {
+        let Some(path) = app
+            .dialog()
+            .file()
+            .set_file_name(&file_name)
+            .add_filter(extension.to_uppercase(), &[extension.as_str()])
+            .blocking_save_file()
+        else {
+            return Ok(false);
+        };
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        let mut file = app.fs().open(path, options).map_err(|e| e.to_string())?;
+        file.write_all(contents.as_bytes())
+            .map_err(|e| e.to_string())?;
+        Ok(true)
+    })
+    .await
+}
+
+/// Asks for a file with the native open dialog and returns its contents, or `None` if the dialog
+/// was cancelled.
+#[tauri::command]
+async fn open_text_file(app: AppHandle, extension: String) -> Result<Option<String>, String> {
+    run_blocking(move || {
+        let Some(path) = app
+            .dialog()
+            .file()
+            .add_filter(extension.to_uppercase(), &[extension.as_str()])
+            .blocking_pick_file()
+        else {
+            return Ok(None);
+        };
+        app.fs()
+            .read_to_string(path)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Runs the dialogs on a thread meant for blocking, as waiting for them on the async runtime
+/// would hold up other commands until they are closed.
+async fn run_blocking<T: Send + 'static>(
+    operation: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(operation)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Turns a portfolio name into a file name by replacing characters that separate paths or are
+/// reserved on some systems.
+fn file_name(name: &str, extension: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Windows drops trailing dots and spaces, leading dots hide files elsewhere, and a name of
+    // only dots refers to a directory
+    let name = name.trim_matches(['.', ' ']);
+    let name = if name.is_empty() { "portfolio" } else { name };
+    format!("{name}.{extension}")
+}
+
+/// Computes the target of each position with the given strategy.
+#[tauri::command]
+fn compute_targets(
+    strategy: StrategyState,
+    positions: PositionsDataStore,
+) -> Result<Vec<TargetPosition>, RebalanceError> {
+    functions::get_target_assets(strategy, positions)
+}
+
+/// Returns everything that prevents rebalancing the positions with the given strategy.
+#[tauri::command]
+fn validate_portfolio(
+    strategy: StrategyState,
+    positions: PositionsDataStore,
+) -> Vec<RebalanceError> {
+    positions.validate(strategy)
+}
+
+/// Asks for a location with the native save dialog and writes the portfolio there as JSON.
+/// Returns `false` if the dialog was cancelled.
+#[tauri::command]
+async fn save_portfolio_file(app: AppHandle, portfolio: Portfolio) -> Result<bool, String> {
+    let contents = serde_json::to_string_pretty(&portfolio).map_err(|e| e.to_string())?;
+    let file_name = file_name(&portfolio.name, "json");
+    save_text_file(app, file_name, "json".to_string(), contents).await
+}
+
+/// Asks for a JSON file with the native open dialog and reads the portfolio from it, or returns
+/// `None` if the dialog was cancelled.
+#[tauri::command]
+async fn open_portfolio_file(app: AppHandle) -> Result<Option<Portfolio>, String> {
+    let Some(contents) = open_text_file(app, "json".to_string()).await? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![])
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .invoke_handler(tauri::generate_handler![
+            save_text_file,
+            open_text_file,
+            compute_targets,
+            validate_portfolio,
+            save_portfolio_file,
+            open_portfolio_file,
+            storage::load_portfolios,
+            storage::save_portfolios
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_replaces_path_separators_and_reserved_characters() {
+        // Arrange
+        let name = "../Stocks: a/b\\c <\"x\"> | *?";
+
+        // Act
+        let file_name = file_name(name, "json");
+
+        // Assert
+        assert_eq!(file_name, "_Stocks_ a_b_c __x__ _ __.json");
+    }
+
+    #[test]
+    fn file_name_falls_back_if_nothing_remains() {
+        // Arrange
+        let name = " .. ";
+
+        // Act
+        let file_name = file_name(name, "json");
+
+        // Assert
+        assert_eq!(file_name, "portfolio.json");
+    }
+}
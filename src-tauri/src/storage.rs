@@ -0,0 +1,138 @@
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const STATE_FILE: &str = "portfolios.json";
+const BACKUP_FILE: &str = "portfolios.backup.json";
+const TEMPORARY_FILE: &str = "portfolios.json.tmp";
+
+/// Returns the stored portfolios, or those of the previous save if `backup` is set. Returns `None`
+/// if nothing has been stored yet. The contents are passed on as they are, so the frontend can
+/// migrate them like any other stored state.
+#[tauri::command]
+pub async fn load_portfolios(app: AppHandle, backup: bool) -> Result<Option<String>, String> {
+    let file = if backup { BACKUP_FILE } else { STATE_FILE };
+    let path = data_dir(&app)?.join(file);
+    run_blocking(move || read_file(&path)).await
+}
+
+/// Stores the portfolios, keeping the previously stored ones as backup unless `replace_backup` is
+/// set. The frontend waits for each save to finish before sending the next one, so they are
+/// written in order.
+#[tauri::command]
+pub async fn save_portfolios(
+    app: AppHandle,
+    contents: String,
+    replace_backup: bool,
+) -> Result<(), String> {
+    let dir = data_dir(&app)?;
+    run_blocking(move || write_state(&dir, &contents, replace_backup)).await
+}
+
+/// Runs file operations on a thread meant for blocking, so they never hold up the main thread.
+async fn run_blocking<T: Send + 'static>(
+    operation: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(operation)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+fn read_file(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Writes to a temporary file that replaces the state file once it is complete, so a crash while
+/// writing never leaves a truncated state behind. With `replace_backup`, the backup is written
+/// with the new contents as well, so no copy of the previous state remains.
+fn write_state(dir: &Path, contents: &str, replace_backup: bool) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let temporary = dir.join(TEMPORARY_FILE);
+    let mut file = fs::File::create(&temporary)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    let state = dir.join(STATE_FILE);
+    let backup = dir.join(BACKUP_FILE);
+    if state.exists() && !replace_backup {
+        // Copied rather than moved, so there is a state file at any time
+        fs::copy(&state, &backup)?;
+    }
+    fs::rename(temporary, &state)?;
+    if replace_backup {
+        fs::copy(&state, backup)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temporary_dir() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        std::env::temp_dir().join(format!("rebalax-storage-{nanos}"))
+    }
+
+    #[test]
+    fn write_state_keeps_previous_state_as_backup() {
+        // Arrange
+        let dir = temporary_dir();
+        write_state(&dir, "first", false).unwrap();
+
+        // Act
+        write_state(&dir, "second", false).unwrap();
+
+        // Assert
+        let state = read_file(&dir.join(STATE_FILE)).unwrap();
+        let backup = read_file(&dir.join(BACKUP_FILE)).unwrap();
+        assert_eq!(state.as_deref(), Some("second"));
+        assert_eq!(backup.as_deref(), Some("first"));
+        assert!(!dir.join(TEMPORARY_FILE).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn write_state_replacing_backup_leaves_no_previous_state() {
+        // Arrange
+        let dir = temporary_dir();
+        write_state(&dir, "plaintext", false).unwrap();
+        write_state(&dir, "plaintext", false).unwrap();
+
+        // Act
+        write_state(&dir, "encrypted", true).unwrap();
+
+        // Assert
+        for file in [STATE_FILE, BACKUP_FILE] {
+            let contents = read_file(&dir.join(file)).unwrap();
+            assert_eq!(contents.as_deref(), Some("encrypted"));
+        }
+        assert!(!dir.join(TEMPORARY_FILE).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_file_returns_none_if_nothing_was_stored() {
+        // Arrange
+        let dir = temporary_dir();
+
+        // Act
+        let result = read_file(&dir.join(STATE_FILE)).unwrap();
+
+        // Assert
+        assert_eq!(result, None);
+    }
+}
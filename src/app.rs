@@ -1,7 +1,11 @@
 use crate::components::*;
+use crate::history::History;
 use crate::i18n::*;
+use crate::lock::{LoadFailedScreen, UnlockScreen};
 use crate::menu::Menu;
+use crate::privacy::{self, use_privacy_mode};
 use crate::rebalancer::Rebalancer;
+use crate::storage::{self, StorageStatus};
 use leptos::prelude::*;
 use leptos_i18n_router::I18nRoute;
 use leptos_router::{components::*, hooks::use_location, path};
@@ -9,17 +13,29 @@ use leptos_router::{components::*, hooks::use_location, path};
 #[component]
 pub fn App() -> impl IntoView {
     leptos_meta::provide_meta_context();
+    privacy::provide_privacy_mode();
+    let storage = storage::provide_portfolios();
+    let status = storage.status();
 
     view! {
         <I18nContextProvider>
             <Router>
                 <TitleBar />
-                <Routes fallback=|| "Not found">
-                    <I18nRoute<Locale, _, _> view=|| view! { <Outlet /> }>
-                        <Route path=path!("/") view=Rebalancer />
-                        <Route path=path!("/menu") view=Menu />
-                    </I18nRoute<Locale, _, _>>
-                </Routes>
+                <Show when=move || matches!(status.get(), StorageStatus::Locked(_))>
+                    <UnlockScreen />
+                </Show>
+                <Show when=move || status.get() == StorageStatus::Failed>
+                    <LoadFailedScreen />
+                </Show>
+                <Show when=move || status.get() == StorageStatus::Ready>
+                    <Routes fallback=|| "Not found">
+                        <I18nRoute<Locale, _, _> view=|| view! { <Outlet /> }>
+                            <Route path=path!("/") view=Rebalancer />
+                            <Route path=path!("/menu") view=Menu />
+                            <Route path=path!("/history") view=History />
+                        </I18nRoute<Locale, _, _>>
+                    </Routes>
+                </Show>
             </Router>
         </I18nContextProvider>
     }
@@ -37,7 +53,10 @@ pub fn TitleBar() -> impl IntoView {
                 <a class="titlebar-button" href="/">
                     <span class="titlebar-title">Rebalax</span>
                 </a>
-                <SwitchMenuButton />
+                <div class="titlebar-actions">
+                    <PrivacyModeButton />
+                    <SwitchMenuButton />
+                </div>
             </div>
         </div>
     }
@@ -46,7 +65,8 @@ pub fn TitleBar() -> impl IntoView {
 #[component]
 pub fn SwitchMenuButton() -> impl IntoView {
     move || {
-        if use_location().pathname.get().contains("menu") {
+        let pathname = use_location().pathname.get();
+        if pathname.contains("menu") || pathname.contains("history") {
             view! {
                 <a class="titlebar-button" id="titlebar-menu" href="/">
                     <CloseIcon />
@@ -63,3 +83,33 @@ pub fn SwitchMenuButton() -> impl IntoView {
         }
     }
 }
+
+#[component]
+pub fn PrivacyModeButton() -> impl IntoView {
+    let i18n = use_i18n();
+    let (is_private, set_private) = use_privacy_mode();
+
+    view! {
+        <button
+            class="titlebar-button"
+            id="titlebar-privacy"
+            title=move || {
+                if is_private.get() {
+                    t_string!(i18n, show_amounts)
+                } else {
+                    t_string!(i18n, hide_amounts)
+                }
+            }
+            aria-pressed=move || is_private.get().to_string()
+            on:click=move |_| set_private.update(|value| *value = !*value)
+        >
+            {move || {
+                if is_private.get() {
+                    view! { <EyeOffIcon /> }.into_any()
+                } else {
+                    view! { <EyeIcon /> }.into_any()
+                }
+            }}
+        </button>
+    }
+}
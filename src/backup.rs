@@ -0,0 +1,208 @@
+use crate::i18n::Locale;
+use crate::storage::{self, StorageError, VersionedState, SCHEMA_VERSION};
+use crate::types::PortfoliosState;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+pub const BACKUP_FORMAT: &str = "rebalax-backup";
+pub const BACKUP_FILE_NAME: &str = "rebalax-backup.json";
+
+/// The complete app state as written to and read from a backup file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub portfolios: PortfoliosState,
+    pub locale: Option<Locale>,
+}
+
+#[derive(Serialize)]
+struct BackupFile<'a> {
+    format: &'a str,
+    locale: Locale,
+    #[serde(flatten)]
+    state: VersionedState<&'a PortfoliosState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackupError {
+    Read(String),
+    Json(String),
+    NotABackup,
+    Storage(StorageError),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::Read(message) => write!(f, "cannot read backup file: {message}"),
+            BackupError::Json(message) => write!(f, "invalid backup file: {message}"),
+            BackupError::NotABackup => write!(f, "the file is not a backup"),
+            BackupError::Storage(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<serde_json::Error> for BackupError {
+    fn from(value: serde_json::Error) -> Self {
+        BackupError::Json(value.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestoreMode {
+    /// Keeps the current portfolios and replaces those also contained in the backup.
+    #[default]
+    Merge,
+    /// Replaces all current portfolios with those of the backup.
+    Overwrite,
+}
+
+/// Names of the portfolios affected by a restore.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RestorePreview {
+    pub added: Vec<String>,
+    pub replaced: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Serializes the portfolios with the current schema version, so the backup can be migrated the
+/// same way as the local storage once the schema changes.
+pub fn create_backup(portfolios: &PortfoliosState, locale: Locale) -> Result<String, BackupError> {
+    Ok(serde_json::to_string_pretty(&BackupFile {
+        format: BACKUP_FORMAT,
+        locale,
+        state: VersionedState {
+            version: SCHEMA_VERSION,
+            data: portfolios,
+        },
+    })?)
+}
+
+pub fn parse_backup(text: &str) -> Result<Backup, BackupError> {
+    let value: Value = serde_json::from_str(text)?;
+    if value.get("format").and_then(Value::as_str) != Some(BACKUP_FORMAT) {
+        return Err(BackupError::NotABackup);
+    }
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(BackupError::NotABackup)?;
+    let data = value.get("data").cloned().unwrap_or(Value::Null);
+    let portfolios = storage::migrate(version, data).map_err(BackupError::Storage)?;
+    let locale = value
+        .get("locale")
+        .and_then(|x| serde_json::from_value(x.clone()).ok());
+    Ok(Backup { portfolios, locale })
+}
+
+pub fn preview_restore(
+    current: &PortfoliosState,
+    backup: &PortfoliosState,
+    mode: RestoreMode,
+) -> RestorePreview {
+    let is_in = |state: &PortfoliosState, id| state.portfolios.iter().any(|x| x.id == id);
+    let (replaced, added) = backup
+        .portfolios
+        .iter()
+        .partition::<Vec<_>, _>(|x| is_in(current, x.id));
+    let removed = match mode {
+        RestoreMode::Merge => vec![],
+        RestoreMode::Overwrite => current
+            .portfolios
+            .iter()
+            .filter(|x| !is_in(backup, x.id))
+            .map(|x| x.name.clone())
+            .collect(),
+    };
+    RestorePreview {
+        added: added.into_iter().map(|x| x.name.clone()).collect(),
+        replaced: replaced.into_iter().map(|x| x.name.clone()).collect(),
+        removed,
+    }
+}
+
+pub fn restore(current: &mut PortfoliosState, backup: PortfoliosState, mode: RestoreMode) {
+    match mode {
+        RestoreMode::Merge => current.merge(backup),
+        RestoreMode::Overwrite => *current = backup,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Portfolio;
+
+    #[test]
+    fn create_backup_roundtrips_through_parse_backup() {
+        // Arrange
+        let mut portfolios = PortfoliosState::default();
+        portfolios.create();
+
+        // Act
+        let text = create_backup(&portfolios, Locale::de).unwrap();
+        let backup = parse_backup(&text).unwrap();
+
+        // Assert
+        assert_eq!(backup.portfolios, portfolios);
+        assert_eq!(backup.locale, Some(Locale::de));
+    }
+
+    #[test]
+    fn parse_backup_rejects_other_files() {
+        // Assert
+        assert_eq!(
+            parse_backup(r#"{"version":2,"data":{}}"#),
+            Err(BackupError::NotABackup)
+        );
+        assert!(matches!(
+            parse_backup("name;value"),
+            Err(BackupError::Json(_))
+        ));
+        assert_eq!(
+            parse_backup(r#"{"format":"rebalax-backup","version":99,"data":{}}"#),
+            Err(BackupError::Storage(StorageError::UnsupportedVersion(99)))
+        );
+    }
+
+    #[test]
+    fn parse_backup_migrates_older_version() {
+        // Arrange
+        let text = r#"{"format":"rebalax-backup","version":1,"data":{"portfolios":[{
+            "id":"0199a9a0-0000-7000-8000-0000000000a1","name":"Old","strategy":"Sell",
+            "positions":{"rows":[{"id":"0199a9a0-0000-7000-8000-000000000001","name":"Stocks",
+                "current_position":"100","target_allocation":"1"}]}
+        }],"active":"0199a9a0-0000-7000-8000-0000000000a1"}}"#;
+
+        // Act
+        let backup = parse_backup(text).unwrap();
+
+        // Assert
+        assert_eq!(backup.locale, None);
+        assert_eq!(backup.portfolios.active().name, "Old");
+    }
+
+    #[test]
+    fn preview_restore_lists_affected_portfolios() {
+        // Arrange
+        let mut current = PortfoliosState::default();
+        current.create();
+        let mut backup = current.clone();
+        backup.portfolios.remove(1);
+        backup
+            .portfolios
+            .push(Portfolio::new("Backup only".to_string()));
+
+        // Act
+        let merge = preview_restore(&current, &backup, RestoreMode::Merge);
+        let overwrite = preview_restore(&current, &backup, RestoreMode::Overwrite);
+
+        // Assert
+        assert_eq!(merge.added, vec!["Backup only".to_string()]);
+        assert_eq!(merge.replaced, vec!["Portfolio 1".to_string()]);
+        assert!(merge.removed.is_empty());
+        assert_eq!(overwrite.removed, vec!["Portfolio 2".to_string()]);
+    }
+}
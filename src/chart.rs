@@ -0,0 +1,280 @@
+use crate::i18n::*;
+use crate::types::{PositionDrift, PositionsDataStore};
+use leptos::prelude::*;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use uuid::Uuid;
+use wasm_bindgen::JsValue;
+use web_sys::js_sys::Date;
+
+const WIDTH: f64 = 320.0;
+const HEIGHT: f64 = 160.0;
+const PADDING_LEFT: f64 = 34.0;
+const PADDING_RIGHT: f64 = 8.0;
+const PADDING_TOP: f64 = 8.0;
+const PADDING_BOTTOM: f64 = 8.0;
+const COLORS: [&str; 8] = [
+    "#3b82f6", "#f59e0b", "#10b981", "#ec4899", "#8b5cf6", "#06b6d4", "#84cc16", "#f97316",
+];
+
+/// Allocations of a portfolio at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftPoint {
+    pub timestamp: i64,
+    pub drifts: Vec<PositionDrift>,
+    pub max_drift: Decimal,
+}
+
+impl DriftPoint {
+    pub fn new(timestamp: i64, positions: &PositionsDataStore) -> Self {
+        Self {
+            timestamp,
+            drifts: positions.drifts(),
+            max_drift: positions.max_drift(),
+        }
+    }
+}
+
+/// Maps timestamps and allocations onto the SVG coordinate space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChartScale {
+    start: i64,
+    end: i64,
+    max: Decimal,
+}
+
+impl ChartScale {
+    /// Fits all `points` horizontally and `values` vertically, rounding the upper bound up to
+    /// the next ten percent.
+    fn new(points: &[DriftPoint], values: impl Iterator<Item = Decimal>) -> Self {
+        let start = points.iter().map(|x| x.timestamp).min().unwrap_or_default();
+        let end = points.iter().map(|x| x.timestamp).max().unwrap_or_default();
+        let max = values.max().unwrap_or_default().max(dec!(0));
+        let max = ((max * dec!(10)).ceil() / dec!(10)).max(dec!(0.1));
+        Self { start, end, max }
+    }
+    fn x(&self, timestamp: i64) -> f64 {
+        let width = WIDTH - PADDING_LEFT - PADDING_RIGHT;
+        if self.end == self.start {
+            PADDING_LEFT + width / 2.0
+        } else {
+            PADDING_LEFT + (timestamp - self.start) as f64 / (self.end - self.start) as f64 * width
+        }
+    }
+    fn y(&self, value: Decimal) -> f64 {
+        let height = HEIGHT - PADDING_TOP - PADDING_BOTTOM;
+        let ratio = (value / self.max)
+            .to_f64()
+            .unwrap_or_default()
+            .clamp(0.0, 1.0);
+        PADDING_TOP + (1.0 - ratio) * height
+    }
+    fn polyline(&self, points: impl Iterator<Item = (i64, Decimal)>) -> String {
+        points
+            .map(|(timestamp, value)| format!("{:.1},{:.1}", self.x(timestamp), self.y(value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Plots the actual against the target allocation of each position over time, followed by the
+/// maximum absolute drift across the portfolio.
+#[component]
+pub fn DriftChart(#[prop(into)] points: Signal<Vec<DriftPoint>>) -> impl IntoView {
+    let i18n = use_i18n();
+    let (selected, set_selected) = signal(None::<Uuid>);
+    // Positions are listed as they currently are, older points of removed ones are not plotted
+    let positions = Memo::new(move |_| {
+        points.with(|points| {
+            points
+                .last()
+                .map(|x| {
+                    x.drifts
+                        .iter()
+                        .enumerate()
+                        .map(|(ix, drift)| {
+                            (drift.id, drift.name.clone(), COLORS[ix % COLORS.len()])
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        })
+    });
+    let visible = move || {
+        positions
+            .get()
+            .into_iter()
+            .filter(|(id, _, _)| selected.get().is_none_or(|x| x == *id))
+            .collect::<Vec<_>>()
+    };
+
+    let allocation_chart = move || {
+        let points = points.get();
+        let visible = visible();
+        let scale = ChartScale::new(
+            &points,
+            points.iter().flat_map(|point| {
+                point
+                    .drifts
+                    .iter()
+                    .filter(|x| visible.iter().any(|(id, _, _)| *id == x.id))
+                    .flat_map(|x| [x.allocation, x.target])
+                    .collect::<Vec<_>>()
+            }),
+        );
+        let series = move |id: Uuid, value: fn(&PositionDrift) -> Decimal| {
+            scale.polyline(points.iter().filter_map(|point| {
+                let drift = point.drifts.iter().find(|x| x.id == id)?;
+                Some((point.timestamp, value(drift)))
+            }))
+        };
+        view! {
+            <svg class="chart" viewBox=format!("0 0 {WIDTH} {HEIGHT}") role="img">
+                <ChartGrid scale />
+                {visible
+                    .into_iter()
+                    .map(|(id, _, color)| {
+                        view! {
+                            <polyline
+                                class="target-line"
+                                points=series(id, |x| x.target)
+                                stroke=color
+                            />
+                            <polyline
+                                class="allocation-line"
+                                points=series(id, |x| x.allocation)
+                                stroke=color
+                            />
+                        }
+                    })
+                    .collect_view()}
+            </svg>
+        }
+    };
+
+    let drift_chart = move || {
+        let points = points.get();
+        let scale = ChartScale::new(&points, points.iter().map(|x| x.max_drift));
+        view! {
+            <svg class="chart" viewBox=format!("0 0 {WIDTH} {HEIGHT}") role="img">
+                <ChartGrid scale />
+                <polyline
+                    class="drift-line"
+                    points=scale.polyline(points.iter().map(|x| (x.timestamp, x.max_drift)))
+                />
+            </svg>
+        }
+    };
+
+    let time_range = move || {
+        let points = points.get();
+        let locale = i18n.get_locale();
+        let start = points.first().map(|x| format_date(x.timestamp, locale));
+        let end = points.last().map(|x| format_date(x.timestamp, locale));
+        view! {
+            <div class="chart-range">
+                <span>{start}</span>
+                <span>{end}</span>
+            </div>
+        }
+    };
+
+    view! {
+        <section class="drift-chart">
+            <h3>{t!(i18n, allocation_over_time)}</h3>
+            <select
+                class="chart-position-select"
+                on:change=move |ev| set_selected.set(event_target_value(&ev).parse::<Uuid>().ok())
+            >
+                <option value="">{t_string!(i18n, all_positions)}</option>
+                {move || {
+                    positions
+                        .get()
+                        .into_iter()
+                        .map(|(id, name, _)| {
+                            view! {
+                                <option value=id.to_string() selected=selected.get() == Some(id)>
+                                    {name}
+                                </option>
+                            }
+                        })
+                        .collect_view()
+                }}
+            </select>
+            {allocation_chart}
+            {time_range}
+            <ul class="chart-legend">
+                {move || {
+                    visible()
+                        .into_iter()
+                        .map(|(_, name, color)| {
+                            view! {
+                                <li>
+                                    <span class="legend-color" style:background-color=color />
+                                    {name}
+                                </li>
+                            }
+                        })
+                        .collect_view()
+                }}
+                <li class="legend-hint">{t!(i18n, chart_legend_hint)}</li>
+            </ul>
+            <h3>{t!(i18n, max_drift)}</h3>
+            {drift_chart}
+            {time_range}
+        </section>
+    }
+}
+
+#[component]
+fn ChartGrid(scale: ChartScale) -> impl IntoView {
+    [dec!(0), scale.max / dec!(2), scale.max]
+        .into_iter()
+        .map(|value| {
+            let y = scale.y(value);
+            view! {
+                <line class="grid-line" x1=PADDING_LEFT x2=WIDTH - PADDING_RIGHT y1=y y2=y />
+                <text class="grid-label" x=PADDING_LEFT - 4.0 y=y + 3.0>
+                    {format!("{} %", (value * dec!(100)).round_dp(1).normalize())}
+                </text>
+            }
+        })
+        .collect_view()
+}
+
+fn format_date(timestamp: i64, locale: Locale) -> String {
+    Date::new(&JsValue::from_f64(timestamp as f64))
+        .to_locale_date_string(locale.as_ref(), &JsValue::UNDEFINED)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chart_scale_fits_points_and_rounds_maximum() {
+        // Arrange
+        let point = |timestamp| DriftPoint {
+            timestamp,
+            drifts: vec![],
+            max_drift: dec!(0),
+        };
+        let points = [point(1000), point(3000)];
+
+        // Act
+        let scale = ChartScale::new(&points, [dec!(0.42), dec!(0.1)].into_iter());
+
+        // Assert
+        assert_eq!(scale.max, dec!(0.5));
+        assert_eq!(scale.x(1000), PADDING_LEFT);
+        assert_eq!(scale.x(3000), WIDTH - PADDING_RIGHT);
+        assert_eq!(scale.y(dec!(0)), HEIGHT - PADDING_BOTTOM);
+        assert_eq!(scale.y(dec!(0.5)), PADDING_TOP);
+        assert_eq!(
+            scale.polyline([(1000, dec!(0.5)), (3000, dec!(0))].into_iter()),
+            format!("{PADDING_LEFT:.1},{PADDING_TOP:.1} 312.0,152.0")
+        );
+    }
+}
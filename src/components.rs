@@ -1,7 +1,12 @@
+use crate::backup::BackupError;
 use crate::i18n::*;
-use crate::types::StrategyState;
+use crate::privacy::{use_privacy_mode, MASK};
+use crate::storage::StorageError;
+use crate::types::{PositionsDataStore, RebalanceError, StrategyState, TargetPosition};
 use leptos::prelude::*;
+use rebalax_core::csv::{CsvError, CsvLineError};
 use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 
 #[component]
 pub fn MenuIcon() -> impl IntoView {
@@ -154,6 +159,114 @@ pub fn PlusMinusIcon() -> impl IntoView {
     }
 }
 
+#[component]
+pub fn CirclePlusIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="24"
+            height="24"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-circle-plus-icon lucide-circle-plus"
+        >
+            <circle cx="12" cy="12" r="10" />
+            <path d="M8 12h8" />
+            <path d="M12 8v8" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn CircleMinusIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="24"
+            height="24"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-circle-minus-icon lucide-circle-minus"
+        >
+            <circle cx="12" cy="12" r="10" />
+            <path d="M8 12h8" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn CopyIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-copy-icon lucide-copy"
+        >
+            <rect width="14" height="14" x="8" y="8" rx="2" ry="2" />
+            <path d="M4 16c-1.1 0-2-.9-2-2V4c0-1.1.9-2 2-2h10c1.1 0 2 .9 2 2" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn DownloadIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-download-icon lucide-download"
+        >
+            <path d="M12 15V3" />
+            <path d="M21 15v4a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2v-4" />
+            <path d="m7 10 5 5 5-5" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn UploadIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-upload-icon lucide-upload"
+        >
+            <path d="M12 3v12" />
+            <path d="m17 8-5-5-5 5" />
+            <path d="M21 15v4a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2v-4" />
+        </svg>
+    }
+}
+
 #[component]
 pub fn CloseIcon() -> impl IntoView {
     view! {
@@ -175,6 +288,50 @@ pub fn CloseIcon() -> impl IntoView {
     }
 }
 
+#[component]
+pub fn EyeIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="24"
+            height="24"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-eye-icon lucide-eye"
+        >
+            <path d="M2.062 12.348a1 1 0 0 1 0-.696 10.75 10.75 0 0 1 19.876 0 1 1 0 0 1 0 .696 10.75 10.75 0 0 1-19.876 0" />
+            <circle cx="12" cy="12" r="3" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn EyeOffIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="24"
+            height="24"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-eye-off-icon lucide-eye-off"
+        >
+            <path d="M10.733 5.076a10.744 10.744 0 0 1 11.205 6.575 1 1 0 0 1 0 .696 10.747 10.747 0 0 1-1.444 2.49" />
+            <path d="M14.084 14.158a3 3 0 0 1-4.242-4.242" />
+            <path d="M17.479 17.499a10.75 10.75 0 0 1-15.417-5.151 1 1 0 0 1 0-.696 10.75 10.75 0 0 1 4.446-5.143" />
+            <path d="m2 2 20 20" />
+        </svg>
+    }
+}
+
 #[component]
 pub fn PrivacyIcon() -> impl IntoView {
     view! {
@@ -200,23 +357,178 @@ pub fn PrivacyIcon() -> impl IntoView {
 }
 
 #[component]
-pub fn DiffString(diff: Decimal, has_braces: bool) -> impl IntoView {
-    if diff.is_zero() {
-        view! { <span class="zero">{"".to_string()}</span> }
-    } else if diff.is_sign_positive() {
+pub fn HistoryIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="24"
+            height="24"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-history-icon lucide-history"
+        >
+            <path d="M3 12a9 9 0 1 0 9-9 9.75 9.75 0 0 0-6.74 2.74L3 8" />
+            <path d="M3 3v5h5" />
+            <path d="M12 7v5l4 2" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn UndoIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-undo-2-icon lucide-undo-2"
+        >
+            <path d="M9 14 4 9l5-5" />
+            <path d="M4 9h10.5a5.5 5.5 0 0 1 5.5 5.5a5.5 5.5 0 0 1-5.5 5.5H11" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn RedoIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-redo-2-icon lucide-redo-2"
+        >
+            <path d="m15 14 5-5-5-5" />
+            <path d="M20 9H9.5A5.5 5.5 0 0 0 4 14.5A5.5 5.5 0 0 0 9.5 20H13" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn GripIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-grip-vertical-icon lucide-grip-vertical"
+        >
+            <circle cx="9" cy="12" r="1" />
+            <circle cx="9" cy="5" r="1" />
+            <circle cx="9" cy="19" r="1" />
+            <circle cx="15" cy="12" r="1" />
+            <circle cx="15" cy="5" r="1" />
+            <circle cx="15" cy="19" r="1" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn ChevronUpIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-chevron-up-icon lucide-chevron-up"
+        >
+            <path d="m18 15-6-6-6 6" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn ChevronDownIcon() -> impl IntoView {
+    view! {
+        <svg
+            xmlns="http://www.w3.org/2000/svg"
+            width="1rem"
+            height="1rem"
+            viewBox="0 0 24 24"
+            fill="none"
+            stroke="currentColor"
+            stroke-width="2"
+            stroke-linecap="round"
+            stroke-linejoin="round"
+            class="lucide lucide-chevron-down-icon lucide-chevron-down"
+        >
+            <path d="m6 9 6 6 6-6" />
+        </svg>
+    }
+}
+
+#[component]
+pub fn DiffString(
+    diff: Decimal,
+    has_braces: bool,
+    #[prop(optional)] no_action: bool,
+    #[prop(optional, into)] unit: Option<String>,
+) -> impl IntoView {
+    let i18n = use_i18n();
+    let unit = unit
+        .filter(|unit| !unit.is_empty())
+        .map(|unit| format!(" {}", unit))
+        .unwrap_or_default();
+
+    if no_action {
         let fmt = if has_braces {
-            format!(" (+{})", diff)
+            format!(" ({})", t_string!(i18n, no_action))
         } else {
-            format!(" +{}", diff)
+            format!(" {}", t_string!(i18n, no_action))
         };
-        view! { <span class="positive">{fmt}</span> }
+        view! { <span class="no-action">{fmt}</span> }.into_any()
+    } else if diff.is_zero() {
+        view! { <span class="zero">{"".to_string()}</span> }.into_any()
     } else {
-        let fmt = if has_braces {
-            format!(" ({})", diff)
+        let (is_private, _) = use_privacy_mode();
+        // Relative diffs like the drift reveal no amounts and stay visible in privacy mode
+        let is_percentage = unit.trim() == "%";
+        let (sign, class) = if diff.is_sign_positive() {
+            ("+", "positive")
         } else {
-            format!(" {}", diff)
+            ("-", "negative")
+        };
+        let fmt = move || {
+            let value = if is_private.get() && !is_percentage {
+                format!("{}{}", sign, MASK)
+            } else {
+                format!("{}{}{}", sign, diff.abs(), unit)
+            };
+            if has_braces {
+                format!(" ({})", value)
+            } else {
+                format!(" {}", value)
+            }
         };
-        view! { <span class="negative">{fmt}</span> }
+        view! { <span class=class>{fmt}</span> }.into_any()
     }
 }
 
@@ -231,6 +543,8 @@ pub fn StrategyOption(strategy: StrategyState, active: bool) -> impl IntoView {
                     StrategyState::Buy => t_string!(i18n, alt_buy),
                     StrategyState::BuySell => t_string!(i18n, alt_buy_sell),
                     StrategyState::Sell => t_string!(i18n, alt_sell),
+                    StrategyState::Contribute(_) => t_string!(i18n, alt_contribute),
+                    StrategyState::Withdraw(_) => t_string!(i18n, alt_withdraw),
                 }}
             </span>
         }
@@ -242,9 +556,184 @@ pub fn StrategyOption(strategy: StrategyState, active: bool) -> impl IntoView {
                     StrategyState::Buy => view! { <PlusIcon /> }.into_any(),
                     StrategyState::BuySell => view! { <PlusMinusIcon /> }.into_any(),
                     StrategyState::Sell => view! { <MinusIcon /> }.into_any(),
+                    StrategyState::Contribute(_) => view! { <CirclePlusIcon /> }.into_any(),
+                    StrategyState::Withdraw(_) => view! { <CircleMinusIcon /> }.into_any(),
                 }}
             </span>
         }
         .into_any()
     }
 }
+
+/// Paired bars per position comparing its current, target and post-rebalance allocation.
+#[component]
+pub fn AllocationComparison(
+    #[prop(into)] positions: Signal<PositionsDataStore>,
+    #[prop(into)] targets: Signal<Option<Vec<TargetPosition>>>,
+) -> impl IntoView {
+    let i18n = use_i18n();
+    let allocations = Memo::new(move |_| {
+        let positions = positions.get();
+        let rebalanced = targets
+            .get()
+            .map(|targets| positions.rebalanced(&targets).drifts());
+        positions
+            .drifts()
+            .into_iter()
+            .map(|drift| {
+                let after = rebalanced
+                    .as_ref()
+                    .and_then(|x| x.iter().find(|y| y.id == drift.id))
+                    .map(|x| x.allocation);
+                (drift, after)
+            })
+            .collect::<Vec<_>>()
+    });
+    // Scale the bars to the largest allocation, so small positions remain visible
+    let max = Memo::new(move |_| {
+        allocations
+            .get()
+            .iter()
+            .flat_map(|(drift, after)| [Some(drift.allocation), Some(drift.target), *after])
+            .flatten()
+            .max()
+            .unwrap_or_default()
+            .max(dec!(0.01))
+    });
+    let bar = move |class: &'static str, label: String, value: Decimal| {
+        let width = format!(
+            "{}%",
+            (value / max.get() * dec!(100)).round_dp(2).max(dec!(0))
+        );
+        view! {
+            <div class=format!("allocation-bar {class}")>
+                <span class="allocation-bar-label">{label}</span>
+                <span class="allocation-bar-track">
+                    <span class="allocation-bar-fill" style:width=width />
+                </span>
+                <span class="allocation-bar-value">
+                    {format!("{} %", (value * dec!(100)).round_dp(1).normalize())}
+                </span>
+            </div>
+        }
+    };
+
+    view! {
+        <section class="allocation-comparison">
+            {move || {
+                allocations
+                    .get()
+                    .into_iter()
+                    .map(|(drift, after)| {
+                        view! {
+                            <div class="allocation-position">
+                                <b>{drift.name}</b>
+                                {bar("current", t_string!(i18n, current).to_string(), drift.allocation)}
+                                {bar("target", t_string!(i18n, target).to_string(), drift.target)}
+                                {after
+                                    .map(|after| {
+                                        bar("after", t_string!(i18n, after_rebalance).to_string(), after)
+                                    })}
+                            </div>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </section>
+    }
+}
+
+#[component]
+pub fn ValidationMessage(error: RebalanceError) -> impl IntoView {
+    let i18n = use_i18n();
+    let (is_private, _) = use_privacy_mode();
+
+    let message = move || match error {
+        RebalanceError::EmptyPortfolio => t_string!(i18n, error_empty_portfolio).to_string(),
+        RebalanceError::AllocationSumMismatch { difference } => t_string!(
+            i18n,
+            error_allocation_sum,
+            sum = ((dec!(1) + difference) * dec!(100)).round_dp(2).normalize(),
+            difference = (difference * dec!(100)).round_dp(2).normalize(),
+        )
+        .to_string(),
+        RebalanceError::GroupAllocationSumMismatch { difference, .. } => t_string!(
+            i18n,
+            error_group_allocation_sum,
+            sum = ((dec!(1) + difference) * dec!(100)).round_dp(2).normalize(),
+            difference = (difference * dec!(100)).round_dp(2).normalize(),
+        )
+        .to_string(),
+        RebalanceError::NegativeGroupAllocation { .. } => {
+            t_string!(i18n, error_negative_group_allocation).to_string()
+        }
+        RebalanceError::NegativeAllocation { .. } => {
+            t_string!(i18n, error_negative_allocation).to_string()
+        }
+        RebalanceError::MissingExchangeRate { .. } => {
+            t_string!(i18n, error_missing_exchange_rate).to_string()
+        }
+        RebalanceError::NegativePosition { .. } => {
+            t_string!(i18n, error_negative_position).to_string()
+        }
+        RebalanceError::ZeroPosition { .. } => t_string!(i18n, error_zero_position).to_string(),
+        RebalanceError::UnknownPosition { .. } => {
+            t_string!(i18n, error_unknown_position).to_string()
+        }
+        RebalanceError::NegativeAmount { .. } => t_string!(i18n, error_negative_amount).to_string(),
+        RebalanceError::AmountExceedsTotal { total, .. } => {
+            let total = if is_private.get() {
+                MASK.to_string()
+            } else {
+                total.round_dp(2).normalize().to_string()
+            };
+            t_string!(i18n, error_amount_exceeds_total, total).to_string()
+        }
+    };
+
+    view! { <span class="validation-message">{message}</span> }
+}
+
+#[component]
+pub fn CsvErrorMessage(error: CsvLineError) -> impl IntoView {
+    let i18n = use_i18n();
+    let line = error.line;
+
+    let message = match error.error {
+        CsvError::MissingColumns { found } => {
+            t_string!(i18n, csv_error_missing_columns, line, found).to_string()
+        }
+        CsvError::EmptyName => t_string!(i18n, csv_error_empty_name, line).to_string(),
+        CsvError::InvalidNumber { value } => {
+            t_string!(i18n, csv_error_invalid_number, line, value).to_string()
+        }
+        CsvError::UnterminatedQuote => {
+            t_string!(i18n, csv_error_unterminated_quote, line).to_string()
+        }
+    };
+
+    view! { <span class="validation-message">{message}</span> }
+}
+
+#[component]
+pub fn BackupErrorMessage(error: BackupError) -> impl IntoView {
+    let i18n = use_i18n();
+
+    let message = match error {
+        BackupError::Read(_) => t_string!(i18n, backup_error_read).to_string(),
+        BackupError::Json(_)
+        | BackupError::Storage(StorageError::Json(_))
+        | BackupError::Storage(StorageError::Crypto(_)) => {
+            t_string!(i18n, backup_error_invalid).to_string()
+        }
+        BackupError::NotABackup => t_string!(i18n, backup_error_not_a_backup).to_string(),
+        BackupError::Storage(StorageError::UnsupportedVersion(version)) => {
+            t_string!(i18n, backup_error_unsupported_version, version).to_string()
+        }
+        BackupError::Storage(StorageError::NoPortfolios) => {
+            t_string!(i18n, backup_error_no_portfolios).to_string()
+        }
+    };
+
+    view! { <span class="validation-message">{message}</span> }
+}
@@ -0,0 +1,213 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+pub const ENCRYPTED_FORMAT: &str = "rebalax-encrypted";
+const SALT_LENGTH: usize = 16;
+
+/// Cost of deriving the key from the passphrase, stored along with the data so it can be raised
+/// later without breaking existing data.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory in KiB
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Stored state encrypted with AES-256-GCM under a key derived from the passphrase with Argon2id.
+/// Binary values are Base64 encoded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EncryptedState {
+    pub format: String,
+    pub kdf: KdfParams,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl EncryptedState {
+    /// Reads the encrypted state from a stored value, or returns `None` if it is not encrypted.
+    pub fn parse(stored: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(stored).ok()?;
+        if value.get("format").and_then(Value::as_str) != Some(ENCRYPTED_FORMAT) {
+            return None;
+        }
+        serde_json::from_value(value).ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoError {
+    KeyDerivation,
+    InvalidData,
+    /// The data could not be decrypted, either because the passphrase is wrong or the data was
+    /// changed.
+    WrongPassphrase,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::KeyDerivation => write!(f, "cannot derive a key from the passphrase"),
+            CryptoError::InvalidData => write!(f, "the encrypted data is malformed"),
+            CryptoError::WrongPassphrase => write!(f, "the passphrase is wrong"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+/// Key derived from a passphrase, kept in memory while the app is unlocked.
+#[derive(Clone, PartialEq)]
+pub struct EncryptionKey {
+    key: [u8; 32],
+    salt: [u8; SALT_LENGTH],
+    kdf: KdfParams,
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey").finish_non_exhaustive()
+    }
+}
+
+impl EncryptionKey {
+    /// Derives a key for newly encrypted data with a random salt.
+    pub fn new(passphrase: &str) -> Result<Self, CryptoError> {
+        Self::with_params(passphrase, KdfParams::default())
+    }
+    pub(crate) fn with_params(passphrase: &str, kdf: KdfParams) -> Result<Self, CryptoError> {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, salt, kdf)
+    }
+    /// Derives the key the given state was encrypted with.
+    pub fn for_state(passphrase: &str, state: &EncryptedState) -> Result<Self, CryptoError> {
+        let salt = BASE64
+            .decode(&state.salt)
+            .ok()
+            .and_then(|x| x.try_into().ok())
+            .ok_or(CryptoError::InvalidData)?;
+        Self::derive(passphrase, salt, state.kdf)
+    }
+    /// Checks whether `passphrase` is the one this key was derived from.
+    pub fn matches(&self, passphrase: &str) -> Result<bool, CryptoError> {
+        Ok(Self::derive(passphrase, self.salt, self.kdf)? == *self)
+    }
+    fn derive(
+        passphrase: &str,
+        salt: [u8; SALT_LENGTH],
+        kdf: KdfParams,
+    ) -> Result<Self, CryptoError> {
+        let params = Params::new(kdf.memory, kdf.iterations, kdf.parallelism, Some(32))
+            .map_err(|_| CryptoError::KeyDerivation)?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|_| CryptoError::KeyDerivation)?;
+        Ok(Self { key, salt, kdf })
+    }
+    /// Encrypts `plaintext` with a fresh nonce.
+    pub fn encrypt(&self, plaintext: &str) -> Result<EncryptedState, CryptoError> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| CryptoError::InvalidData)?;
+        Ok(EncryptedState {
+            format: ENCRYPTED_FORMAT.to_string(),
+            kdf: self.kdf,
+            salt: BASE64.encode(self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+    pub fn decrypt(&self, state: &EncryptedState) -> Result<String, CryptoError> {
+        let decode = |value: &str| BASE64.decode(value).map_err(|_| CryptoError::InvalidData);
+        let nonce = decode(&state.nonce)?;
+        if nonce.len() != 12 {
+            return Err(CryptoError::InvalidData);
+        }
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key));
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                decode(&state.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| CryptoError::WrongPassphrase)?;
+        String::from_utf8(plaintext).map_err(|_| CryptoError::InvalidData)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Keeps the tests fast, the stored parameters are used for decryption anyway
+    pub(crate) const TEST_PARAMS: KdfParams = KdfParams {
+        memory: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn encryption_key_roundtrip_with_passphrase() {
+        // Arrange
+        let key = EncryptionKey::with_params("correct horse", TEST_PARAMS).unwrap();
+
+        // Act
+        let encrypted = key.encrypt(r#"{"version":2}"#).unwrap();
+        let stored = serde_json::to_string(&encrypted).unwrap();
+        let parsed = EncryptedState::parse(&stored).unwrap();
+        let unlocked = EncryptionKey::for_state("correct horse", &parsed).unwrap();
+
+        // Assert
+        assert!(!stored.contains("version"));
+        assert_eq!(unlocked, key);
+        assert_eq!(unlocked.decrypt(&parsed).unwrap(), r#"{"version":2}"#);
+    }
+
+    #[test]
+    fn encryption_key_rejects_wrong_passphrase() {
+        // Arrange
+        let key = EncryptionKey::with_params("correct horse", TEST_PARAMS).unwrap();
+        let encrypted = key.encrypt("secret").unwrap();
+
+        // Act
+        let result = EncryptionKey::for_state("battery staple", &encrypted)
+            .and_then(|x| x.decrypt(&encrypted));
+
+        // Assert
+        assert_eq!(result, Err(CryptoError::WrongPassphrase));
+        assert_eq!(key.matches("battery staple"), Ok(false));
+        assert_eq!(key.matches("correct horse"), Ok(true));
+    }
+
+    #[test]
+    fn encrypted_state_is_only_parsed_from_encrypted_values() {
+        // Act
+        let plain = EncryptedState::parse(r#"{"version":2,"data":{}}"#);
+        let invalid = EncryptedState::parse("not json");
+
+        // Assert
+        assert_eq!(plain, None);
+        assert_eq!(invalid, None);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::tauri;
+use crate::types::{PositionsDataStore, RebalanceError, StrategyState, TargetPosition};
+use rebalax_core::functions;
+use rust_decimal::Decimal;
+
+/// Targets of the positions together with the fees expected for trading towards them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rebalance {
+    pub targets: Vec<TargetPosition>,
+    pub expected_fees: Decimal,
+}
+
+/// Computes the targets in the native backend on Tauri and in WASM in the browser, or if the
+/// backend cannot be reached.
+pub async fn compute_targets(
+    strategy: StrategyState,
+    positions: PositionsDataStore,
+) -> Result<Vec<TargetPosition>, RebalanceError> {
+    if tauri::is_tauri() {
+        match tauri::compute_targets(strategy, &positions).await {
+            Ok(result) => return result,
+            Err(error) => log::error!("{error}"),
+        }
+    }
+    functions::get_target_assets(strategy, positions)
+}
+
+/// Computes the targets like [`compute_targets`] along with the fees expected for them.
+pub async fn compute_rebalance(
+    strategy: StrategyState,
+    positions: PositionsDataStore,
+) -> Result<Rebalance, RebalanceError> {
+    let targets = compute_targets(strategy, positions.clone()).await?;
+    let expected_fees = functions::get_expected_fees(&positions, &targets);
+    Ok(Rebalance {
+        targets,
+        expected_fees,
+    })
+}
+
+/// Validates the positions in the native backend on Tauri and in WASM in the browser, or if the
+/// backend cannot be reached.
+pub async fn validate_portfolio(
+    strategy: StrategyState,
+    positions: PositionsDataStore,
+) -> Vec<RebalanceError> {
+    if tauri::is_tauri() {
+        match tauri::validate_portfolio(strategy, &positions).await {
+            Ok(errors) => return errors,
+            Err(error) => log::error!("{error}"),
+        }
+    }
+    positions.validate(strategy)
+}
@@ -0,0 +1,264 @@
+use crate::chart::{DriftChart, DriftPoint};
+use crate::components::*;
+use crate::i18n::*;
+use crate::portfolios::PortfolioSwitcher;
+use crate::privacy::Amount;
+use crate::storage;
+use crate::types::{PositionChange, Snapshot, SnapshotReason};
+use leptos::prelude::*;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use uuid::Uuid;
+use wasm_bindgen::JsValue;
+use web_sys::js_sys::Date;
+
+#[component]
+pub fn History() -> impl IntoView {
+    let i18n = use_i18n();
+    let (portfolios, set_portfolios) = storage::use_portfolios();
+    let active_id = Memo::new(move |_| portfolios.get().active().id);
+    let snapshots = Memo::new(move |_| portfolios.get().active().snapshots.clone());
+    let (from, set_from) = signal(None::<Uuid>);
+    let (to, set_to) = signal(None::<Uuid>);
+
+    // Compare the two most recent snapshots unless the user picked others
+    let selected = move || {
+        let snapshots = snapshots.get();
+        let latest = |skip: usize| snapshots.iter().rev().nth(skip).map(|x| x.id);
+        let is_known = |id: &Uuid| snapshots.iter().any(|x| x.id == *id);
+        let from = from.get().filter(is_known).or(latest(1));
+        let to = to.get().filter(is_known).or(latest(0));
+        (from, to)
+    };
+
+    // The current state is plotted as the latest valuation
+    let drift_points = Signal::derive(move || {
+        portfolios.with(|value| {
+            let portfolio = value.active();
+            portfolio
+                .snapshots
+                .iter()
+                .map(|x| DriftPoint::new(x.timestamp, &x.positions))
+                .chain([DriftPoint::new(now(), &portfolio.positions)])
+                .collect::<Vec<_>>()
+        })
+    });
+
+    let save_snapshot = move |_| {
+        let portfolio_id = active_id.get_untracked();
+        set_portfolios.update(|value| {
+            if let Some(portfolio) = value.portfolio_mut(portfolio_id) {
+                portfolio.record_snapshot(SnapshotReason::Manual, now());
+            }
+        })
+    };
+
+    let remove_snapshot = move |id: Uuid| {
+        let portfolio_id = active_id.get_untracked();
+        set_portfolios.update(|value| {
+            if let Some(portfolio) = value.portfolio_mut(portfolio_id) {
+                portfolio.remove_snapshot(id);
+            }
+        })
+    };
+
+    let snapshot_rows = move || {
+        let (selected_from, selected_to) = selected();
+        snapshots
+            .get()
+            .into_iter()
+            .rev()
+            .map(|snapshot| {
+                let id = snapshot.id;
+                let reason = match snapshot.reason {
+                    SnapshotReason::Manual => t_string!(i18n, snapshot_manual),
+                    SnapshotReason::Rebalance => t_string!(i18n, snapshot_rebalance),
+                };
+                let total = snapshot.positions.total().round_dp(0).to_string();
+                view! {
+                    <tr>
+                        <td>
+                            <div class="snapshot-date">
+                                {format_timestamp(snapshot.timestamp, i18n.get_locale())}
+                            </div>
+                            <div class="snapshot-reason">{reason}</div>
+                        </td>
+                        <td class="number">
+                            <Amount>{total.clone()}</Amount>
+                        </td>
+                        <td class="snapshot-select">
+                            <input
+                                type="radio"
+                                name="snapshot-from"
+                                title=t_string!(i18n, snapshot_from)
+                                checked=selected_from == Some(id)
+                                on:change=move |_| set_from.set(Some(id))
+                            />
+                            <input
+                                type="radio"
+                                name="snapshot-to"
+                                title=t_string!(i18n, snapshot_to)
+                                checked=selected_to == Some(id)
+                                on:change=move |_| set_to.set(Some(id))
+                            />
+                            <button
+                                class="remove-position"
+                                title=t_string!(i18n, remove_snapshot)
+                                on:click=move |_| remove_snapshot(id)
+                            >
+                                <DeleteIcon />
+                            </button>
+                        </td>
+                    </tr>
+                }
+            })
+            .collect_view()
+    };
+
+    let snapshot_diff = move || {
+        let (Some(from), Some(to)) = selected() else {
+            return None;
+        };
+        let snapshots = snapshots.get();
+        let find = |id| snapshots.iter().find(|x: &&Snapshot| x.id == id);
+        let (from, to) = (find(from)?, find(to)?);
+        let changes = to.positions.changes_since(&from.positions);
+        let total = to.positions.total().round_dp(0).to_string();
+        let total_diff = (to.positions.total() - from.positions.total()).round_dp(0);
+        Some(view! {
+            <section class="snapshot-diff">
+                <h3>
+                    {format!(
+                        "{} → {}",
+                        format_timestamp(from.timestamp, i18n.get_locale()),
+                        format_timestamp(to.timestamp, i18n.get_locale()),
+                    )}
+                </h3>
+                <table>
+                    {changes
+                        .into_iter()
+                        .map(|change| view! { <PositionChangeRows change /> })
+                        .collect_view()}
+                </table>
+                <section class="total">
+                    <b>{t!(i18n, total)}</b>
+                    <span>
+                        <Amount>{total.clone()}</Amount>
+                        <DiffString diff=total_diff has_braces=true />
+                    </span>
+                </section>
+            </section>
+        })
+    };
+
+    let trade_log = move || {
+        portfolios
+            .get()
+            .active()
+            .trades
+            .iter()
+            .rev()
+            .map(|entry| {
+                view! {
+                    <div class="trade-log-entry">
+                        <b>{format_timestamp(entry.timestamp, i18n.get_locale())}</b>
+                        <ul>
+                            {entry
+                                .trades
+                                .iter()
+                                .map(|trade| {
+                                    view! {
+                                        <li>
+                                            {format!("{}:", trade.name)}
+                                            <DiffString diff=trade.amount.normalize() has_braces=false />
+                                        </li>
+                                    }
+                                })
+                                .collect_view()}
+                        </ul>
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <main>
+            <PortfolioSwitcher portfolios set_portfolios />
+            <section class="snapshot-actions">
+                <button class="save-snapshot" on:click=save_snapshot>
+                    <PlusIcon />
+                    {t!(i18n, save_snapshot)}
+                </button>
+            </section>
+            <Show
+                when=move || !snapshots.get().is_empty()
+                fallback=move || view! { <p class="no-snapshots">{t!(i18n, no_snapshots)}</p> }
+            >
+                <table class="snapshots">{snapshot_rows}</table>
+            </Show>
+            {snapshot_diff}
+            <Show when=move || !snapshots.get().is_empty()>
+                <DriftChart points=drift_points />
+            </Show>
+            <Show when=move || portfolios.with(|value| !value.active().trades.is_empty())>
+                <section class="trade-log">
+                    <h3>{t!(i18n, trade_log)}</h3>
+                    {trade_log}
+                </section>
+            </Show>
+        </main>
+    }
+}
+
+#[component]
+fn PositionChangeRows(change: PositionChange) -> impl IntoView {
+    let i18n = use_i18n();
+    let value = change.value_after.round_dp(0).to_string();
+    let value_diff = (change.value_after - change.value_before).round_dp(0);
+    let allocation_diff = percentage(change.allocation_after - change.allocation_before);
+
+    view! {
+        <tr>
+            <td colspan=3 class="title">
+                <b>{change.name}</b>
+            </td>
+        </tr>
+        <tr class="current">
+            <td>{t_string!(i18n, value)}</td>
+            <td class="number">
+                <div class="number">
+                    <Amount>{value.clone()}</Amount>
+                    <DiffString diff=value_diff has_braces=true />
+                </div>
+            </td>
+            <td class="number">
+                <div class="number percentage">{percentage(change.allocation_after).to_string()}</div>
+            </td>
+        </tr>
+        <tr class="drift">
+            <td>{t_string!(i18n, allocation_change)}</td>
+            <td class="number">
+                <div class="number">
+                    <DiffString diff=allocation_diff has_braces=false unit="%" />
+                </div>
+            </td>
+            <td />
+        </tr>
+    }
+}
+
+fn percentage(value: Decimal) -> Decimal {
+    (value * dec!(100)).round_dp(2).normalize()
+}
+
+/// Current time in milliseconds since the Unix epoch.
+pub fn now() -> i64 {
+    Date::now() as i64
+}
+
+fn format_timestamp(timestamp: i64, locale: Locale) -> String {
+    Date::new(&JsValue::from_f64(timestamp as f64))
+        .to_locale_string(locale.as_ref(), &JsValue::UNDEFINED)
+        .into()
+}
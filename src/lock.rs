@@ -0,0 +1,228 @@
+use crate::crypto::CryptoError;
+use crate::i18n::*;
+use crate::storage::{self, StorageError};
+use leptos::prelude::*;
+
+#[component]
+pub fn UnlockScreen() -> impl IntoView {
+    let i18n = use_i18n();
+    let storage = storage::use_portfolio_storage();
+    let (passphrase, set_passphrase) = signal(String::new());
+    let (error, set_error) = signal(None::<StorageError>);
+
+    let unlock = move || {
+        let result = storage.unlock(&passphrase.get_untracked());
+        if let Err(error) = &result {
+            log::error!("{error}");
+        }
+        set_error.set(result.err());
+    };
+
+    let error_message = move || {
+        error.get().map(|error| {
+            let message = match error {
+                StorageError::Crypto(CryptoError::WrongPassphrase) => {
+                    t_string!(i18n, passphrase_wrong).to_string()
+                }
+                _ => t_string!(i18n, unlock_error).to_string(),
+            };
+            view! { <span class="validation-message">{message}</span> }
+        })
+    };
+
+    view! {
+        <main>
+            <section class="unlock">
+                <h2>{t!(i18n, unlock_title)}</h2>
+                <p>{t!(i18n, unlock_hint)}</p>
+                <input
+                    type="password"
+                    autocomplete="current-password"
+                    placeholder=move || t_string!(i18n, passphrase)
+                    prop:value=passphrase
+                    on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                    on:keydown=move |ev| {
+                        if ev.key() == "Enter" {
+                            unlock();
+                        }
+                    }
+                />
+                {error_message}
+                <button class="unlock-button" on:click=move |_| unlock()>
+                    {t!(i18n, unlock)}
+                </button>
+                <ResetPortfolios>{t!(i18n, forgot_passphrase)}</ResetPortfolios>
+            </section>
+        </main>
+    }
+}
+
+/// Shown instead of the portfolios if they are stored but cannot be read.
+#[component]
+pub fn LoadFailedScreen() -> impl IntoView {
+    let i18n = use_i18n();
+
+    view! {
+        <main>
+            <section class="unlock">
+                <h2>{t!(i18n, load_failed_title)}</h2>
+                <p>{t!(i18n, load_failed_hint)}</p>
+                <ResetPortfolios>{t!(i18n, start_over)}</ResetPortfolios>
+            </section>
+        </main>
+    }
+}
+
+/// Button replacing the stored portfolios with the defaults after a warning.
+#[component]
+fn ResetPortfolios(children: ChildrenFn) -> impl IntoView {
+    let i18n = use_i18n();
+    let storage = storage::use_portfolio_storage();
+    let (confirm_reset, set_confirm_reset) = signal(false);
+
+    view! {
+        <Show
+            when=move || confirm_reset.get()
+            fallback=move || {
+                view! {
+                    <button class="forgot-passphrase" on:click=move |_| set_confirm_reset.set(true)>
+                        {children()}
+                    </button>
+                }
+            }
+        >
+            <p class="validation-message">{t!(i18n, reset_warning)}</p>
+            <div class="restore-actions">
+                <button class="apply-reset" on:click=move |_| storage.reset()>
+                    {t!(i18n, reset_apply)}
+                </button>
+                <button on:click=move |_| set_confirm_reset.set(false)>{t!(i18n, cancel)}</button>
+            </div>
+        </Show>
+    }
+}
+
+/// Outcome of the last change in [`PassphraseSettings`].
+#[derive(Debug, Clone, PartialEq)]
+enum PassphraseChange {
+    Saved,
+    Removed,
+    Empty,
+    Mismatch,
+    Failed(StorageError),
+}
+
+#[component]
+pub fn PassphraseSettings() -> impl IntoView {
+    let i18n = use_i18n();
+    let storage = storage::use_portfolio_storage();
+    let is_encrypted = storage.is_encrypted();
+    let (current, set_current) = signal(String::new());
+    let (new, set_new) = signal(String::new());
+    let (confirmation, set_confirmation) = signal(String::new());
+    let (change, set_change) = signal(None::<PassphraseChange>);
+
+    let apply = move |remove: bool| {
+        let new_passphrase = new.get_untracked();
+        let result = if remove {
+            storage
+                .set_passphrase(&current.get_untracked(), None)
+                .map(|_| PassphraseChange::Removed)
+        } else if new_passphrase.is_empty() {
+            Ok(PassphraseChange::Empty)
+        } else if new_passphrase != confirmation.get_untracked() {
+            Ok(PassphraseChange::Mismatch)
+        } else {
+            storage
+                .set_passphrase(&current.get_untracked(), Some(&new_passphrase))
+                .map(|_| PassphraseChange::Saved)
+        };
+        let change = result.unwrap_or_else(|error| {
+            log::error!("{error}");
+            PassphraseChange::Failed(error)
+        });
+        if matches!(change, PassphraseChange::Saved | PassphraseChange::Removed) {
+            set_current.set(String::new());
+            set_new.set(String::new());
+            set_confirmation.set(String::new());
+        }
+        set_change.set(Some(change));
+    };
+
+    let change_message = move || {
+        let message = match change.get()? {
+            PassphraseChange::Saved => {
+                return Some(view! { <p>{t!(i18n, passphrase_saved)}</p> }.into_any());
+            }
+            PassphraseChange::Removed => {
+                return Some(view! { <p>{t!(i18n, passphrase_removed)}</p> }.into_any());
+            }
+            PassphraseChange::Empty => t_string!(i18n, passphrase_empty).to_string(),
+            PassphraseChange::Mismatch => t_string!(i18n, passphrase_mismatch).to_string(),
+            PassphraseChange::Failed(StorageError::Crypto(CryptoError::WrongPassphrase)) => {
+                t_string!(i18n, passphrase_wrong).to_string()
+            }
+            PassphraseChange::Failed(_) => t_string!(i18n, passphrase_error).to_string(),
+        };
+        Some(view! { <span class="validation-message">{message}</span> }.into_any())
+    };
+
+    let password_input = move |label: String,
+                               autocomplete: &'static str,
+                               value: ReadSignal<String>,
+                               set_value: WriteSignal<String>| {
+        view! {
+            <input
+                type="password"
+                autocomplete=autocomplete
+                placeholder=label
+                prop:value=value
+                on:input=move |ev| set_value.set(event_target_value(&ev))
+            />
+        }
+    };
+
+    view! {
+        <section class="passphrase">
+            <b>{t!(i18n, passphrase_lock)}</b>
+            <p>{t!(i18n, passphrase_lock_hint)}</p>
+            <Show when=move || is_encrypted.get()>
+                {password_input(
+                    t_string!(i18n, current_passphrase).to_string(),
+                    "current-password",
+                    current,
+                    set_current,
+                )}
+            </Show>
+            {password_input(
+                t_string!(i18n, new_passphrase).to_string(),
+                "new-password",
+                new,
+                set_new,
+            )}
+            {password_input(
+                t_string!(i18n, confirm_passphrase).to_string(),
+                "new-password",
+                confirmation,
+                set_confirmation,
+            )}
+            {change_message}
+            <div class="restore-actions">
+                <button class="set-passphrase" on:click=move |_| apply(false)>
+                    {move || {
+                        if is_encrypted.get() {
+                            t_string!(i18n, change_passphrase)
+                        } else {
+                            t_string!(i18n, set_passphrase)
+                        }
+                    }}
+                </button>
+                <Show when=move || is_encrypted.get()>
+                    <button class="remove-passphrase" on:click=move |_| apply(true)>
+                        {t!(i18n, remove_passphrase)}
+                    </button>
+                </Show>
+            </div>
+        </section>
+    }
+}
@@ -1,9 +1,21 @@
 mod app;
+mod backup;
+mod chart;
 mod components;
-mod functions;
+mod crypto;
+mod engine;
+mod history;
+mod lock;
 mod menu;
+mod portfolios;
+mod privacy;
 mod rebalancer;
+mod storage;
+mod tauri;
+mod transfer;
 mod types;
+mod undo;
+mod view_settings;
 include!(concat!(env!("OUT_DIR"), "/i18n/mod.rs"));
 
 use app::*;
@@ -1,5 +1,7 @@
 use crate::components::*;
 use crate::i18n::{t, use_i18n, Locale};
+use crate::lock::PassphraseSettings;
+use crate::transfer::BackupTransfer;
 use leptos::prelude::*;
 
 #[component]
@@ -9,6 +11,10 @@ pub fn Menu() -> impl IntoView {
     view! {
         <main>
             <nav>
+                <a class="menu-item" href="/history">
+                    <HistoryIcon />
+                    {t!(i18n, history)}
+                </a>
                 <a
                     class="menu-item"
                     href="https://github.com/reifenrath-dev/rebalax"
@@ -38,6 +44,8 @@ pub fn Menu() -> impl IntoView {
                 </a>
                 <SwitchLang />
             </nav>
+            <BackupTransfer />
+            <PassphraseSettings />
         </main>
     }
 }
@@ -0,0 +1,104 @@
+use crate::components::*;
+use crate::i18n::*;
+use crate::types::PortfoliosState;
+use leptos::prelude::*;
+use uuid::Uuid;
+
+#[component]
+pub fn PortfolioSwitcher(
+    portfolios: Signal<PortfoliosState>,
+    set_portfolios: WriteSignal<PortfoliosState>,
+) -> impl IntoView {
+    let i18n = use_i18n();
+    let active_id = Memo::new(move |_| portfolios.get().active().id);
+    let portfolio_names = Memo::new(move |_| {
+        portfolios
+            .get()
+            .portfolios
+            .iter()
+            .map(|portfolio| (portfolio.id, portfolio.name.clone()))
+            .collect::<Vec<_>>()
+    });
+
+    let portfolio_select = move || {
+        view! {
+            <select
+                id="portfolio-select"
+                title=t_string!(i18n, portfolio)
+                on:change=move |ev| {
+                    if let Ok(id) = event_target_value(&ev).parse::<Uuid>() {
+                        set_portfolios.update(|value| value.active = id)
+                    }
+                }
+            >
+                {portfolio_names
+                    .get()
+                    .into_iter()
+                    .map(|(id, name)| {
+                        view! {
+                            <option value=id.to_string() selected=move || active_id.get() == id>
+                                {name}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+        }
+    };
+
+    let rename_input = move || {
+        let portfolio_id = active_id.get();
+        view! {
+            <input
+                class="portfolio-name-input"
+                type="text"
+                title=t_string!(i18n, rename_portfolio)
+                value=portfolios.get_untracked().active().name.clone()
+                on:input=move |ev| {
+                    let name = event_target_value(&ev);
+                    set_portfolios
+                        .update(|value| {
+                            if let Some(portfolio) = value.portfolio_mut(portfolio_id) {
+                                portfolio.name = name;
+                            }
+                        })
+                }
+            />
+        }
+    };
+
+    view! {
+        <section class="portfolios">
+            {portfolio_select}
+            <div class="portfolio-actions">
+                {rename_input}
+                <button
+                    class="create-portfolio"
+                    title=t_string!(i18n, create_portfolio)
+                    on:click=move |_| set_portfolios.update(|value| value.create())
+                >
+                    <PlusIcon />
+                </button>
+                <button
+                    class="duplicate-portfolio"
+                    title=t_string!(i18n, duplicate_portfolio)
+                    on:click=move |_| {
+                        set_portfolios.update(|value| value.duplicate(active_id.get_untracked()))
+                    }
+                >
+                    <CopyIcon />
+                </button>
+                <button
+                    class="remove-portfolio"
+                    title=t_string!(i18n, remove_portfolio)
+                    disabled=move || portfolios.get().portfolios.len() <= 1
+                    on:click=move |_| {
+                        set_portfolios.update(|value| value.remove(active_id.get_untracked()))
+                    }
+                >
+                    <DeleteIcon />
+                </button>
+            </div>
+        </section>
+    }
+}
@@ -0,0 +1,46 @@
+use codee::string::FromToStringCodec;
+use leptos::prelude::*;
+use leptos_use::storage::use_local_storage;
+
+const PRIVACY_MODE_KEY: &str = "privacy-mode";
+
+/// Shown instead of an absolute amount while privacy mode is on.
+pub const MASK: &str = "•••";
+
+/// Whether absolute amounts are hidden. Percentages and drift stay visible either way.
+#[derive(Clone, Copy)]
+struct PrivacyMode {
+    is_private: Signal<bool>,
+    set_private: WriteSignal<bool>,
+}
+
+/// Provides the privacy mode, which is kept in local storage apart from the portfolios so it
+/// applies before they are unlocked.
+pub fn provide_privacy_mode() {
+    let (is_private, set_private, _) =
+        use_local_storage::<bool, FromToStringCodec>(PRIVACY_MODE_KEY);
+    provide_context(PrivacyMode {
+        is_private,
+        set_private,
+    });
+}
+
+pub fn use_privacy_mode() -> (Signal<bool>, WriteSignal<bool>) {
+    let privacy_mode = expect_context::<PrivacyMode>();
+    (privacy_mode.is_private, privacy_mode.set_private)
+}
+
+/// Absolute amount that is masked in privacy mode.
+#[component]
+pub fn Amount(children: ChildrenFn) -> impl IntoView {
+    let (is_private, _) = use_privacy_mode();
+
+    view! {
+        <Show
+            when=move || !is_private.get()
+            fallback=|| view! { <span class="masked">{MASK}</span> }
+        >
+            {children()}
+        </Show>
+    }
+}
@@ -1,28 +1,326 @@
 use crate::components::*;
-use crate::functions;
+use crate::engine;
+use crate::history;
 use crate::i18n::*;
-use crate::types::{PositionInputState, PositionsDataStore, StrategyState};
-use codee::string::JsonSerdeCodec;
+use crate::portfolios::PortfolioSwitcher;
+use crate::privacy::{use_privacy_mode, Amount, MASK};
+use crate::storage;
+use crate::transfer::{CsvTransfer, PortfolioTransfer};
+use crate::types::{
+    AllocationGroup, AllocationNode, AppliedRebalance, ApplyOptions, Portfolio, PortfoliosState,
+    PositionInputState, PositionSort, PositionsDataStore, StrategyState, Trade,
+};
+use crate::undo::UndoHistory;
+use crate::view_settings::{use_view_settings, ViewSettings};
 use leptos::prelude::*;
-use leptos_use::storage::use_local_storage;
+use leptos::task::spawn_local;
+use rebalax_core::csv::{self, ImportedPosition};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::time::Duration;
 use strum::IntoEnumIterator;
 use uuid::Uuid;
 
+/// How long the toast offering to undo a deletion is shown.
+const UNDO_TOAST_DURATION: Duration = Duration::from_secs(6);
+
+/// Toast offering to undo the deletion of `name`.
+#[derive(Debug, Clone)]
+struct UndoToast {
+    id: usize,
+    name: String,
+    // Undo step of the deletion, known once it is recorded
+    step: Option<usize>,
+}
+
 #[component]
 pub fn Rebalancer() -> impl IntoView {
     let i18n = use_i18n();
-    let (strategy, set_strategy, _) =
-        use_local_storage::<StrategyState, JsonSerdeCodec>("strategy-state");
+    let (portfolios, set_portfolios) = storage::use_portfolios();
+    let active_id = Memo::new(move |_| portfolios.get().active().id);
+    // Bumped after the active portfolio was replaced from outside, so its view is rebuilt
+    let (revision, set_revision) = signal(0usize);
+
+    let import_positions = Callback::new(move |imported: Vec<ImportedPosition>| {
+        set_portfolios.update(|value| {
+            let active_id = value.active().id;
+            if let Some(portfolio) = value.portfolio_mut(active_id) {
+                csv::apply_import(&mut portfolio.positions, &imported);
+            }
+        });
+        set_revision.update(|value| *value += 1);
+    });
+
+    let import_portfolio = Callback::new(move |portfolio: Portfolio| {
+        set_portfolios.update(|value| value.import(portfolio));
+    });
+
+    // Last applied rebalance, which can be undone until the next one is applied
+    let (applied, set_applied) = signal(None::<AppliedRebalance>);
+
+    let apply_rebalance = Callback::new(move |(trades, options): (Vec<Trade>, ApplyOptions)| {
+        let mut result = None;
+        set_portfolios.update(|value| {
+            let active_id = value.active().id;
+            if let Some(portfolio) = value.portfolio_mut(active_id) {
+                result = Some(portfolio.apply_rebalance(trades, options, history::now()));
+            }
+        });
+        set_applied.set(result);
+        set_revision.update(|value| *value += 1);
+    });
+
+    let undo_rebalance = move |_| {
+        let Some(applied) = applied.get_untracked() else {
+            return;
+        };
+        set_portfolios.update(|value| {
+            if let Some(portfolio) = value.portfolio_mut(applied.portfolio) {
+                portfolio.undo_rebalance(applied);
+            }
+        });
+        set_applied.set(None);
+        set_revision.update(|value| *value += 1);
+    };
+
+    // Previous positions and strategy of the active portfolio
+    let undo_history = RwSignal::new(UndoHistory::<(StrategyState, PositionsDataStore)>::default());
+    // Set while undo or redo replace the state, so the replacement is not recorded as a change
+    let is_restoring = StoredValue::new(false);
+    let (undo_toast, set_undo_toast) = signal(None::<UndoToast>);
+    let toast_count = StoredValue::new(0usize);
+
+    Effect::new(
+        move |previous: Option<(Uuid, StrategyState, PositionsDataStore)>| {
+            let current = portfolios.with(|value| {
+                let portfolio = value.active();
+                (
+                    portfolio.id,
+                    portfolio.strategy,
+                    portfolio.positions.clone(),
+                )
+            });
+            if let Some((id, strategy, positions)) = previous {
+                if id != current.0 {
+                    undo_history.update(UndoHistory::clear);
+                    set_undo_toast.set(None);
+                } else if !is_restoring.get_value()
+                    && (strategy != current.1 || positions != current.2)
+                {
+                    undo_history
+                        .update(|value| value.record((strategy, positions), history::now()));
+                    match undo_toast.get_untracked() {
+                        Some(toast) if toast.step.is_none() => {
+                            // Later changes become steps of their own, so the toast undoes only
+                            // the deletion
+                            let mut step = None;
+                            undo_history.update(|value| {
+                                value.seal();
+                                step = value.last_step();
+                            });
+                            set_undo_toast.set(Some(UndoToast { step, ..toast }));
+                        }
+                        Some(toast)
+                            if undo_history.with_untracked(|x| x.last_step()) != toast.step =>
+                        {
+                            set_undo_toast.set(None);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            is_restoring.set_value(false);
+            current
+        },
+    );
+
+    let restore = move |state: Option<(StrategyState, PositionsDataStore)>| {
+        let Some((strategy, positions)) = state else {
+            return;
+        };
+        is_restoring.set_value(true);
+        set_portfolios.update(|value| {
+            let active_id = value.active().id;
+            if let Some(portfolio) = value.portfolio_mut(active_id) {
+                portfolio.strategy = strategy;
+                portfolio.positions = positions;
+            }
+        });
+        set_undo_toast.set(None);
+        set_revision.update(|value| *value += 1);
+    };
+    let current_state = move || {
+        portfolios
+            .with_untracked(|value| (value.active().strategy, value.active().positions.clone()))
+    };
+    let undo = move || {
+        let mut state = None;
+        undo_history.update(|value| state = value.undo(current_state()));
+        restore(state);
+    };
+    let redo = move || {
+        let mut state = None;
+        undo_history.update(|value| state = value.redo(current_state()));
+        restore(state);
+    };
+
+    let keydown = window_event_listener(leptos::ev::keydown, move |ev| {
+        if !(ev.ctrl_key() || ev.meta_key()) {
+            return;
+        }
+        match ev.key().to_lowercase().as_str() {
+            "z" if ev.shift_key() => {
+                ev.prevent_default();
+                redo();
+            }
+            "z" => {
+                ev.prevent_default();
+                undo();
+            }
+            "y" => {
+                ev.prevent_default();
+                redo();
+            }
+            _ => {}
+        }
+    });
+    on_cleanup(move || keydown.remove());
+
+    let undo_deletion = move |step: Option<usize>| {
+        let Some(step) = step else {
+            return;
+        };
+        let mut state = None;
+        undo_history.update(|value| state = value.undo_step(step, current_state()));
+        restore(state);
+    };
+
+    let show_undo_toast = Callback::new(move |name: String| {
+        // The deletion becomes its own step, so the toast does not undo edits made before it
+        undo_history.update(UndoHistory::seal);
+        toast_count.update_value(|value| *value += 1);
+        let id = toast_count.get_value();
+        set_undo_toast.set(Some(UndoToast {
+            id,
+            name,
+            step: None,
+        }));
+        set_timeout(
+            move || {
+                if undo_toast.with_untracked(|value| value.as_ref().is_some_and(|x| x.id == id)) {
+                    set_undo_toast.set(None);
+                }
+            },
+            UNDO_TOAST_DURATION,
+        );
+    });
+
+    view! {
+        <main>
+            <PortfolioSwitcher portfolios set_portfolios />
+            <section class="undo-redo">
+                <button
+                    class="undo"
+                    title=t_string!(i18n, undo_shortcut)
+                    disabled=move || !undo_history.with(UndoHistory::can_undo)
+                    on:click=move |_| undo()
+                >
+                    <UndoIcon />
+                </button>
+                <button
+                    class="redo"
+                    title=t_string!(i18n, redo_shortcut)
+                    disabled=move || !undo_history.with(UndoHistory::can_redo)
+                    on:click=move |_| redo()
+                >
+                    <RedoIcon />
+                </button>
+            </section>
+            <Show when=move || {
+                applied.with(|value| value.as_ref().is_some_and(|x| x.portfolio == active_id.get()))
+            }>
+                <section class="rebalance-applied">
+                    <span>{t!(i18n, rebalance_applied)}</span>
+                    <button class="undo-rebalance" on:click=undo_rebalance>
+                        {t!(i18n, undo)}
+                    </button>
+                </section>
+            </Show>
+            {move || {
+                let portfolio_id = active_id.get();
+                revision.track();
+                view! {
+                    <PortfolioRebalancer
+                        portfolio_id
+                        portfolios
+                        set_portfolios
+                        on_apply=apply_rebalance
+                        on_remove=show_undo_toast
+                    />
+                }
+            }}
+            <CsvTransfer portfolios on_import=import_positions />
+            <PortfolioTransfer portfolios on_import=import_portfolio />
+            {move || {
+                undo_toast
+                    .get()
+                    .map(|toast| {
+                        view! {
+                            <div class="undo-toast" role="status">
+                                <span>{t_string!(i18n, removed_item, name = toast.name)}</span>
+                                <button on:click=move |_| undo_deletion(toast.step)>
+                                    {t!(i18n, undo)}
+                                </button>
+                            </div>
+                        }
+                    })
+            }}
+        </main>
+    }
+}
+
+#[component]
+fn PortfolioRebalancer(
+    portfolio_id: Uuid,
+    portfolios: Signal<PortfoliosState>,
+    set_portfolios: WriteSignal<PortfoliosState>,
+    #[prop(into)] on_apply: Callback<(Vec<Trade>, ApplyOptions)>,
+    #[prop(into)] on_remove: Callback<String>,
+) -> impl IntoView {
+    let i18n = use_i18n();
+    let (is_private, _) = use_privacy_mode();
+    let portfolio = portfolios.get_untracked().active().clone();
+    let (strategy, set_strategy) = signal(portfolio.strategy);
+    let (positions, set_positions) = signal(portfolio.positions);
+    let (view_settings, set_view_settings) = use_view_settings(portfolio_id);
+    let update_view_settings = move |update: &dyn Fn(&mut ViewSettings)| {
+        let mut value = view_settings.get_untracked();
+        update(&mut value);
+        set_view_settings.run(value);
+    };
 
-    let (positions, set_positions, _) =
-        use_local_storage::<PositionsDataStore, JsonSerdeCodec>("asset-state");
+    Effect::new(move |_| {
+        let strategy = strategy.get();
+        let positions = positions.get();
+        set_portfolios.update(|value| {
+            if let Some(portfolio) = value.portfolio_mut(portfolio_id) {
+                portfolio.strategy = strategy;
+                portfolio.positions = positions;
+            }
+        })
+    });
 
     // Value Functions
     let position_total = move || positions.get().total();
 
-    let target_positions = move || functions::get_target_assets(strategy.get(), positions.get());
+    // Computed by the engine once per change and shared by everything showing targets
+    let rebalance =
+        LocalResource::new(move || engine::compute_rebalance(strategy.get(), positions.get()));
+    let rebalance = Memo::new(move |_| rebalance.get().and_then(Result::ok));
+    let target_positions =
+        move || rebalance.with(|value| value.as_ref().map(|x| x.targets.clone()));
+
+    let validation_errors = Memo::new(move |_| positions.get().validate(strategy.get()));
 
     let strategy_options = {
         view! {
@@ -35,12 +333,22 @@ pub fn Rebalancer() -> impl IntoView {
                                 name="strategy"
                                 id=format!("strategy-{}", stra)
                                 value=stra.to_string()
-                                checked=move || strategy.get() == stra
-                                on:change=move |_| set_strategy.set(stra)
+                                checked=move || strategy.get().is_same_kind(&stra)
+                                on:change=move |_| {
+                                    let amount = strategy
+                                        .get_untracked()
+                                        .amount()
+                                        .unwrap_or_default();
+                                    set_strategy.set(stra.with_amount(amount))
+                                }
                                 alt=match stra {
                                     StrategyState::BuySell => t_string!(i18n, alt_buy_sell),
                                     StrategyState::Buy => t_string!(i18n, alt_buy),
                                     StrategyState::Sell => t_string!(i18n, alt_sell),
+                                    StrategyState::Contribute(_) => {
+                                        t_string!(i18n, alt_contribute)
+                                    }
+                                    StrategyState::Withdraw(_) => t_string!(i18n, alt_withdraw),
                                 }
                             />
                             <label for=format!(
@@ -48,7 +356,7 @@ pub fn Rebalancer() -> impl IntoView {
                                 stra,
                             )>
                                 {move || {
-                                    let active = strategy.get() == stra;
+                                    let active = strategy.get().is_same_kind(&stra);
                                     view! { <StrategyOption strategy=stra active /> }
                                 }}
                             </label>
@@ -59,65 +367,592 @@ pub fn Rebalancer() -> impl IntoView {
         }
     };
 
-    let position_table_rows = {
+    let strategy_amount = Memo::new(move |_| strategy.get().amount());
+
+    let strategy_amount_input = move || {
+        let amount = strategy_amount.get_untracked().unwrap_or_default();
         view! {
-            <For
-                each=move || positions.get().rows.clone()
-                key=|row| row.id
-                children=move |position| {
-                    view! {
-                        <tr>
-                            <td colspan=3 class="title">
-                                <div class="title-input-container">
-                                    <input
-                                        class="title-input"
-                                        type="text"
-                                        value=position.name
-                                        on:input=move |ev| {
-                                            let mut new_positions = positions.get().rows;
-                                            new_positions
-                                                .iter_mut()
-                                                .find(|x| x.id == position.id)
-                                                .unwrap()
-                                                .name = event_target_value(&ev).parse().unwrap();
-                                            set_positions
-                                                .set(PositionsDataStore {
-                                                    rows: new_positions,
-                                                })
-                                        }
-                                    />
-                                    <button
-                                        class="remove-position"
-                                        on:click=move |_| {
-                                            set_positions
-                                                .update(|value| {
-                                                    let ix = value
-                                                        .rows
-                                                        .iter()
-                                                        .position(|x| x.id == position.id)
-                                                        .unwrap();
-                                                    value.rows.remove(ix);
-                                                })
+            <section class="amount">
+                <b>{t!(i18n, amount)}:</b>
+                <input
+                    id="strategy-amount-input"
+                    min="0"
+                    max="9999999"
+                    placeholder="..."
+                    type="number"
+                    class:masked=is_private
+                    value=if amount.is_zero() { "".to_string() } else { amount.to_string() }
+                    on:input=move |ev| {
+                        let amount = event_target_value(&ev).parse::<Decimal>().unwrap_or(dec!(0));
+                        set_strategy.update(|value| *value = value.with_amount(amount))
+                    }
+                />
+            </section>
+        }
+    };
+
+    let only_outside_tolerance = Memo::new(move |_| positions.get().only_outside_tolerance);
+
+    let tolerance_options = {
+        view! {
+            <section class="tolerance">
+                <input
+                    type="checkbox"
+                    id="only-outside-tolerance"
+                    checked=move || only_outside_tolerance.get()
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        set_positions.update(|value| value.only_outside_tolerance = checked)
+                    }
+                />
+                <label for="only-outside-tolerance">{t!(i18n, only_outside_tolerance)}</label>
+                <Show when=move || only_outside_tolerance.get()>
+                    <div class="tolerance-inputs">
+                        <span class="percentage-input">
+                            <input
+                                id="tolerance-absolute-input"
+                                min="0"
+                                max="100"
+                                placeholder="..."
+                                title=t_string!(i18n, tolerance_absolute)
+                                type="number"
+                                class="percentage"
+                                value=percentage_value(positions.get_untracked().tolerance.absolute)
+                                on:input=move |ev| {
+                                    let absolute = parse_percentage(&ev);
+                                    set_positions
+                                        .update(|value| value.tolerance.absolute = absolute)
+                                }
+                            />
+                        </span>
+                        <span class="percentage-input">
+                            <input
+                                id="tolerance-relative-input"
+                                min="0"
+                                max="100"
+                                placeholder="..."
+                                title=t_string!(i18n, tolerance_relative)
+                                type="number"
+                                class="percentage"
+                                value=percentage_value(positions.get_untracked().tolerance.relative)
+                                on:input=move |ev| {
+                                    let relative = parse_percentage(&ev);
+                                    set_positions
+                                        .update(|value| value.tolerance.relative = relative)
+                                }
+                            />
+                        </span>
+                    </div>
+                </Show>
+            </section>
+        }
+    };
+
+    let consider_fees = Memo::new(move |_| positions.get().consider_fees);
+
+    let fee_options = {
+        view! {
+            <section class="fees">
+                <input
+                    type="checkbox"
+                    id="consider-fees"
+                    checked=move || consider_fees.get()
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        set_positions.update(|value| value.consider_fees = checked)
+                    }
+                />
+                <label for="consider-fees">{t!(i18n, consider_fees)}</label>
+                <Show when=move || consider_fees.get()>
+                    <div class="fee-inputs">
+                        <input
+                            id="fee-flat-input"
+                            min="0"
+                            max="9999999"
+                            placeholder="..."
+                            title=t_string!(i18n, fee_flat)
+                            type="number"
+                            value=amount_value(positions.get_untracked().fees.flat)
+                            on:input=move |ev| {
+                                let flat = parse_amount(&ev);
+                                set_positions.update(|value| value.fees.flat = flat)
+                            }
+                        />
+                        <span class="percentage-input">
+                            <input
+                                id="fee-percentage-input"
+                                min="0"
+                                max="100"
+                                placeholder="..."
+                                title=t_string!(i18n, fee_percentage)
+                                type="number"
+                                class="percentage"
+                                value=percentage_value(positions.get_untracked().fees.percentage)
+                                on:input=move |ev| {
+                                    let percentage = parse_percentage(&ev);
+                                    set_positions
+                                        .update(|value| value.fees.percentage = percentage)
+                                }
+                            />
+                        </span>
+                        <input
+                            id="fee-minimum-order-input"
+                            min="0"
+                            max="9999999"
+                            placeholder="..."
+                            title=t_string!(i18n, minimum_order)
+                            type="number"
+                            value=amount_value(positions.get_untracked().fees.minimum_order)
+                            on:input=move |ev| {
+                                let minimum_order = parse_amount(&ev);
+                                set_positions
+                                    .update(|value| value.fees.minimum_order = minimum_order)
+                            }
+                        />
+                    </div>
+                </Show>
+            </section>
+        }
+    };
+
+    let show_units = Memo::new(move |_| view_settings.with(|value| value.show_units));
+
+    let units_options = {
+        view! {
+            <section class="units">
+                <input
+                    type="checkbox"
+                    id="show-units"
+                    checked=move || show_units.get()
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        update_view_settings(&|value| value.show_units = checked)
+                    }
+                />
+                <label for="show-units">{t!(i18n, show_units)}</label>
+            </section>
+        }
+    };
+
+    let show_currencies = Memo::new(move |_| view_settings.with(|value| value.show_currencies));
+    let exchange_rate_count =
+        Memo::new(move |_| positions.with(|value| value.exchange_rates.len()));
+    let currencies = Memo::new(move |_| positions.with(|value| value.currencies()));
+
+    let exchange_rate_rows = move || {
+        (0..exchange_rate_count.get())
+            .map(|ix| {
+                let rate = positions.with_untracked(|value| value.exchange_rates[ix].clone());
+                view! {
+                    <div class="exchange-rate">
+                        <input
+                            class="currency-input"
+                            type="text"
+                            maxlength="3"
+                            placeholder="USD"
+                            title=t_string!(i18n, currency)
+                            value=rate.currency
+                            on:input=move |ev| {
+                                let currency = event_target_value(&ev).trim().to_uppercase();
+                                set_positions.update(|value| value.exchange_rates[ix].currency = currency)
+                            }
+                        />
+                        <input
+                            min="0"
+                            max="9999999"
+                            placeholder="..."
+                            title=t_string!(i18n, exchange_rate_hint)
+                            type="number"
+                            value=amount_value(Some(rate.rate).filter(|x| !x.is_zero()))
+                            on:input=move |ev| {
+                                let rate = parse_amount(&ev).unwrap_or_default();
+                                set_positions.update(|value| value.exchange_rates[ix].rate = rate)
+                            }
+                        />
+                        <button
+                            class="remove-position"
+                            title=t_string!(i18n, remove_exchange_rate)
+                            on:click=move |_| {
+                                let name = positions
+                                    .with_untracked(|value| value.exchange_rates[ix].currency.clone());
+                                on_remove.run(name);
+                                set_positions.update(|value| { value.exchange_rates.remove(ix); })
+                            }
+                        >
+                            <DeleteIcon />
+                        </button>
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+
+    let currency_options = {
+        view! {
+            <section class="currencies">
+                <input
+                    type="checkbox"
+                    id="show-currencies"
+                    checked=move || show_currencies.get()
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        update_view_settings(&|value| value.show_currencies = checked)
+                    }
+                />
+                <label for="show-currencies">{t!(i18n, show_currencies)}</label>
+                <Show when=move || show_currencies.get()>
+                    <div class="currency-inputs">
+                        <input
+                            id="base-currency-input"
+                            class="currency-input"
+                            type="text"
+                            maxlength="3"
+                            placeholder="EUR"
+                            title=t_string!(i18n, base_currency)
+                            value=positions.get_untracked().base_currency.unwrap_or_default()
+                            on:input=move |ev| {
+                                let currency = event_target_value(&ev).trim().to_uppercase();
+                                set_positions
+                                    .update(|value| {
+                                        value.base_currency = Some(currency)
+                                            .filter(|x| !x.is_empty());
+                                    })
+                            }
+                        />
+                    </div>
+                    <div class="exchange-rates">
+                        <b>{t!(i18n, exchange_rates)}:</b>
+                        {exchange_rate_rows}
+                        <button
+                            class="add-exchange-rate"
+                            title=t_string!(i18n, add_exchange_rate)
+                            on:click=move |_| {
+                                set_positions
+                                    .update(|value| value.exchange_rates.push(Default::default()))
+                            }
+                        >
+                            <PlusIcon />
+                        </button>
+                    </div>
+                </Show>
+            </section>
+        }
+    };
+
+    // Currency a position is held in, if it differs from the base currency
+    let foreign_currency = move |id: Uuid| {
+        positions.with(|value| {
+            let currency = value
+                .rows
+                .iter()
+                .find(|x| x.id == id)
+                .and_then(|x| x.currency.clone())?
+                .to_uppercase();
+            let base = value.base_currency.as_deref().unwrap_or_default();
+            (!currency.eq_ignore_ascii_case(base.trim())).then_some(currency)
+        })
+    };
+
+    let show_chart = Memo::new(move |_| view_settings.with(|value| value.show_chart));
+
+    let chart_options = {
+        view! {
+            <section class="units">
+                <input
+                    type="checkbox"
+                    id="show-chart"
+                    checked=move || show_chart.get()
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        update_view_settings(&|value| value.show_chart = checked)
+                    }
+                />
+                <label for="show-chart">{t!(i18n, show_chart)}</label>
+            </section>
+        }
+    };
+
+    let has_groups = Memo::new(move |_| positions.with(|value| !value.groups.is_empty()));
+    let group_names = Memo::new(move |_| {
+        positions.with(|value| {
+            value
+                .groups
+                .iter()
+                .map(|group| (group.id, group.name.clone()))
+                .collect::<Vec<_>>()
+        })
+    });
+    // Saved with the portfolio, so it is neither undone nor part of the positions
+    let sort = Memo::new(move |_| {
+        portfolios.with(|value| {
+            value
+                .portfolios
+                .iter()
+                .find(|x| x.id == portfolio_id)
+                .map(|x| x.sort)
+                .unwrap_or_default()
+        })
+    });
+    let set_sort = move |sort: PositionSort| {
+        set_portfolios.update(|value| {
+            if let Some(portfolio) = value.portfolio_mut(portfolio_id) {
+                portfolio.sort = sort;
+            }
+        })
+    };
+    let allocation_tree = Memo::new(move |_| {
+        view_settings.with(|view| {
+            positions.with(|value| value.allocation_tree(sort.get(), &view.collapsed_groups))
+        })
+    });
+
+    let sort_options = {
+        view! {
+            <section class="sort">
+                <label for="position-sort">{t!(i18n, sort)}:</label>
+                <select
+                    id="position-sort"
+                    on:change=move |ev| {
+                        if let Ok(sort) = event_target_value(&ev).parse::<PositionSort>() {
+                            set_sort(sort)
+                        }
+                    }
+                >
+                    {PositionSort::iter()
+                        .map(|option| {
+                            let label = match option {
+                                PositionSort::Manual => t_string!(i18n, sort_manual),
+                                PositionSort::Name => t_string!(i18n, sort_name),
+                                PositionSort::Value => t_string!(i18n, sort_value),
+                                PositionSort::Drift => t_string!(i18n, sort_drift),
+                                PositionSort::Target => t_string!(i18n, sort_target),
+                            };
+                            view! {
+                                <option value=option.to_string() selected=move || sort.get() == option>
+                                    {label}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+            </section>
+        }
+    };
+
+    // Position dragged by its handle and the one it would be dropped in front of
+    let (dragging, set_dragging) = signal(None::<Uuid>);
+    let (drop_target, set_drop_target) = signal(None::<Uuid>);
+    let end_drag = move || {
+        set_dragging.set(None);
+        set_drop_target.set(None);
+    };
+    // Moves positions starting from the displayed order, which becomes the manual one
+    let reorder = move |reorder: &dyn Fn(&mut PositionsDataStore, PositionSort)| {
+        let sort = sort.get_untracked();
+        set_positions.update(|value| reorder(value, sort));
+        set_sort(PositionSort::Manual);
+    };
+
+    let position_rows = move |position: PositionInputState, depth: usize| {
+        let has_unit_price = Memo::new(move |_| {
+            positions
+                .get()
+                .rows
+                .iter()
+                .find(|x| x.id == position.id)
+                .is_some_and(|x| x.unit_price().is_some())
+        });
+        let target_of = move || {
+            target_positions().and_then(|targets| targets.into_iter().find(|x| x.id == position.id))
+        };
+        let current_of = move || {
+            positions
+                .get()
+                .rows
+                .iter()
+                .find(|x| x.id == position.id)
+                .map(|x| x.current_position)
+                .unwrap_or_default()
+        };
+        let is_dragged = move || dragging.get() == Some(position.id);
+        let is_drop_target =
+            move || drop_target.get() == Some(position.id) && dragging.get() != Some(position.id);
+        view! {
+            <tr
+                data-position=position.id.to_string()
+                class:dragging=is_dragged
+                class:drop-target=is_drop_target
+            >
+                <td colspan=3 class="title">
+                    <div class="title-input-container" style:margin-left=indentation(depth)>
+                        <span
+                            class="drag-handle"
+                            title=t_string!(i18n, drag_position)
+                            on:pointerdown=move |ev| {
+                                let handle: web_sys::Element = event_target(&ev);
+                                let _ = handle.set_pointer_capture(ev.pointer_id());
+                                set_dragging.set(Some(position.id));
+                            }
+                            on:pointermove=move |ev| {
+                                if let Some(id) = dragging.get_untracked() {
+                                    let before = position_at(ev.client_x(), ev.client_y())
+                                        .filter(|before| {
+                                            positions.with_untracked(|value| {
+                                                value.can_move_position_to(id, *before)
+                                            })
+                                        });
+                                    set_drop_target.set(before);
+                                }
+                            }
+                            on:pointerup=move |ev| {
+                                let before = position_at(ev.client_x(), ev.client_y());
+                                if let (Some(id), Some(before)) = (dragging.get_untracked(), before) {
+                                    reorder(&|value, sort| {
+                                        value.move_position_to(id, before, sort)
+                                    });
+                                }
+                                end_drag();
+                            }
+                            on:pointercancel=move |_| end_drag()
+                        >
+                            <GripIcon />
+                        </span>
+                        <input
+                            class="title-input"
+                            type="text"
+                            value=position.name
+                            on:input=move |ev| {
+                                let mut new_positions = positions.get().rows;
+                                new_positions
+                                    .iter_mut()
+                                    .find(|x| x.id == position.id)
+                                    .unwrap()
+                                    .name = event_target_value(&ev).parse().unwrap();
+                                set_positions.update(|value| value.rows = new_positions)
+                            }
+                        />
+                        <button
+                            class="move-position"
+                            title=t_string!(i18n, move_up)
+                            on:click=move |_| {
+                                reorder(&|value, sort| {
+                                    value.move_position(position.id, true, sort)
+                                })
+                            }
+                        >
+                            <ChevronUpIcon />
+                        </button>
+                        <button
+                            class="move-position"
+                            title=t_string!(i18n, move_down)
+                            on:click=move |_| {
+                                reorder(&|value, sort| {
+                                    value.move_position(position.id, false, sort)
+                                })
+                            }
+                        >
+                            <ChevronDownIcon />
+                        </button>
+                        <button
+                            class="remove-position"
+                            on:click=move |_| {
+                                let name = positions
+                                    .with_untracked(|value| {
+                                        value
+                                            .rows
+                                            .iter()
+                                            .find(|x| x.id == position.id)
+                                            .map(|x| x.name.clone())
+                                            .unwrap_or_default()
+                                    });
+                                on_remove.run(name);
+                                set_positions
+                                    .update(|value| {
+                                        let ix = value
+                                            .rows
+                                            .iter()
+                                            .position(|x| x.id == position.id)
+                                            .unwrap();
+                                        value.rows.remove(ix);
+                                    })
+                            }
+                        >
+                            <DeleteIcon />
+                        </button>
+                    </div>
+                    <Show when=move || has_groups.get()>
+                        <select
+                            class="group-select"
+                            title=t_string!(i18n, group)
+                            on:change=move |ev| {
+                                let group = event_target_value(&ev).parse::<Uuid>().ok();
+                                set_positions
+                                    .update(|value| {
+                                        value
+                                            .rows
+                                            .iter_mut()
+                                            .find(|x| x.id == position.id)
+                                            .unwrap()
+                                            .group = group;
+                                    })
+                            }
+                        >
+                            <option value="">{t_string!(i18n, no_group)}</option>
+                            {move || {
+                                let current_group = positions
+                                    .with(|value| {
+                                        value
+                                            .rows
+                                            .iter()
+                                            .find(|x| x.id == position.id)
+                                            .and_then(|x| x.group)
+                                    });
+                                group_names
+                                    .get()
+                                    .into_iter()
+                                    .map(|(id, name)| {
+                                        view! {
+                                            <option
+                                                value=id.to_string()
+                                                selected=current_group == Some(id)
+                                            >
+                                                {name}
+                                            </option>
                                         }
-                                    >
-                                        <DeleteIcon />
-                                    </button>
-                                </div>
-                            </td>
-                        </tr>
-                        <tr class="current">
-                            <td>{t_string!(i18n, current)}</td>
-                            <td class="number">
+                                    })
+                                    .collect_view()
+                            }}
+                        </select>
+                    </Show>
+                </td>
+            </tr>
+            <tr class="current" data-position=position.id.to_string()>
+                <td>{t_string!(i18n, current)}</td>
+                <td class="number">
+                    <Show
+                        when=move || has_unit_price.get()
+                        fallback=move || {
+                            let current_position = positions
+                                .with_untracked(|value| {
+                                    value
+                                        .rows
+                                        .iter()
+                                        .find(|x| x.id == position.id)
+                                        .map(|x| x.current_position)
+                                        .unwrap_or_default()
+                                });
+                            view! {
                                 <input
                                     id=format!("{}-position-input", position.id)
                                     min="0"
                                     max="9999999"
                                     placeholder="..."
                                     type="number"
-                                    value=if position.current_position.is_zero() {
+                                    class:masked=is_private
+                                    value=if current_position.is_zero() {
                                         "".to_string()
                                     } else {
-                                        position.current_position.round_dp(0).to_string()
+                                        current_position.round_dp(0).to_string()
                                     }
                                     on:input=move |ev| {
                                         let mut new_positions = positions.get().rows;
@@ -129,86 +964,615 @@ pub fn Rebalancer() -> impl IntoView {
                                             .parse::<Decimal>()
                                             .unwrap_or(dec!(0));
                                         set_positions
-                                            .set(PositionsDataStore {
-                                                rows: new_positions,
-                                            })
+                                            .update(|value| value.rows = new_positions)
                                     }
                                 />
-                            </td>
-                            <td class="number">
-                                <div class="number percentage">
-                                    {move || {
-                                        (positions.get().allocation_for(position.id) * dec!(100))
-                                            .round_dp(2)
-                                            .to_string()
-                                    }}
-                                </div>
-                            </td>
-                        </tr>
-                        <tr class="target">
-                            <td>{t_string!(i18n, target)}</td>
-                            <td class="number">
-                                <div class="number">
-                                    {move || {
-                                        target_positions()
-                                            .iter()
+                            }
+                        }
+                    >
+                        <div class="number">
+                            <Amount>
+                                {move || {
+                                    positions
+                                        .get()
+                                        .rows
+                                        .iter()
+                                        .find(|x| x.id == position.id)
+                                        .map(|x| x.current_position.round_dp(0).to_string())
+                                }}
+                            </Amount>
+                        </div>
+                    </Show>
+                </td>
+                <td class="number">
+                    <div class="number percentage">
+                        {move || {
+                            positions
+                                .get()
+                                .relative_allocation_for(position.id)
+                                .map(|x| (x * dec!(100)).round_dp(2).to_string())
+                                .unwrap_or_default()
+                        }}
+                    </div>
+                </td>
+            </tr>
+            <tr class="target" data-position=position.id.to_string()>
+                <td>{t_string!(i18n, target)}</td>
+                <td class="number">
+                    <div class="number">
+                        <Amount>
+                            {move || {
+                                target_of()
+                                    .map(|x| x.value)
+                                    .unwrap_or(current_of())
+                                    .round_dp(0)
+                                    .to_string()
+                            }}
+                        </Amount>
+                        {move || {
+                            let diff = target_of()
+                                .map(|x| (x.value - current_of()).round_dp(0))
+                                .unwrap_or_default();
+                            // Only positions the engine actually left untouched, which it does
+                            // not for fixed amounts
+                            let no_action = positions.get().only_outside_tolerance
+                                && positions.get().is_within_tolerance(position.id)
+                                && target_of().is_some_and(|x| {
+                                    (x.value - current_of()).round_dp(2).is_zero()
+                                });
+                            let unit = foreign_currency(position.id).unwrap_or_default();
+                            view! { <DiffString diff has_braces=true no_action unit /> }
+                        }}
+                        {move || {
+                            let target_units = target_of().and_then(|x| x.units)?;
+                            let current_units = positions
+                                .get()
+                                .rows
+                                .iter()
+                                .find(|x| x.id == position.id)
+                                .and_then(|x| x.units)
+                                .unwrap_or_default();
+                            let diff = target_units - current_units;
+                            Some(
+                                view! {
+                                    <DiffString
+                                        diff
+                                        has_braces=true
+                                        unit=t_string!(i18n, units_short)
+                                    />
+                                },
+                            )
+                        }}
+
+                    </div>
+                </td>
+                <td class="number">
+                    <input
+                        id=format!("{}-target-input", position.id)
+                        min="0.01"
+                        max="100"
+                        placeholder="..."
+                        type="number"
+                        class="percentage"
+                        value=if position.target_allocation.is_zero() {
+                            "".to_string()
+                        } else {
+                            (position.target_allocation * dec!(100))
+                                .round_dp(2)
+                                .to_string()
+                        }
+                        on:input=move |ev| {
+                            let mut new_positions = positions.get().rows;
+                            new_positions
+                                .iter_mut()
+                                .find(|x| x.id == position.id)
+                                .unwrap()
+                                .target_allocation = event_target_value(&ev)
+                                .parse::<Decimal>()
+                                .unwrap_or(dec!(0)) / dec!(100);
+                            set_positions.update(|value| value.rows = new_positions)
+                        }
+                    />
+                </td>
+            </tr>
+            {move || {
+                validation_errors
+                    .get()
+                    .into_iter()
+                    .filter(|error| error.position_id() == Some(position.id))
+                    .map(|error| {
+                        view! {
+                            <tr class="validation">
+                                <td colspan=3>
+                                    <ValidationMessage error />
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()
+            }}
+            <Show when=move || only_outside_tolerance.get()>
+                <tr class="tolerance">
+                    <td>{t_string!(i18n, tolerance)}</td>
+                    <td class="number percentage">
+                        <input
+                            id=format!("{}-tolerance-absolute-input", position.id)
+                            min="0"
+                            max="100"
+                            placeholder=move || {
+                                percentage_placeholder(
+                                    positions.get().tolerance.absolute,
+                                )
+                            }
+                            title=t_string!(i18n, tolerance_absolute)
+                            type="number"
+                            class="percentage"
+                            value=percentage_value(position.tolerance.absolute)
+                            on:input=move |ev| {
+                                let absolute = parse_percentage(&ev);
+                                set_positions
+                                    .update(|value| {
+                                        value
+                                            .rows
+                                            .iter_mut()
                                             .find(|x| x.id == position.id)
                                             .unwrap()
-                                            .value
-                                            .round_dp(0)
-                                            .to_string()
-                                    }}
-                                    {move || {
-                                        let diff = (target_positions()
-                                            .iter()
+                                            .tolerance
+                                            .absolute = absolute;
+                                    })
+                            }
+                        />
+                    </td>
+                    <td class="number">
+                        <input
+                            id=format!("{}-tolerance-relative-input", position.id)
+                            min="0"
+                            max="100"
+                            placeholder=move || {
+                                percentage_placeholder(
+                                    positions.get().tolerance.relative,
+                                )
+                            }
+                            title=t_string!(i18n, tolerance_relative)
+                            type="number"
+                            class="percentage"
+                            value=percentage_value(position.tolerance.relative)
+                            on:input=move |ev| {
+                                let relative = parse_percentage(&ev);
+                                set_positions
+                                    .update(|value| {
+                                        value
+                                            .rows
+                                            .iter_mut()
                                             .find(|x| x.id == position.id)
                                             .unwrap()
-                                            .value
-                                            - positions
-                                                .get()
-                                                .rows
-                                                .iter()
-                                                .find(|x| x.id == position.id)
-                                                .unwrap()
-                                                .current_position)
-                                            .round_dp(0);
-                                        view! { <DiffString diff has_braces=true /> }
-                                    }}
-
-                                </div>
-                            </td>
-                            <td class="number">
-                                <input
-                                    id=format!("{}-target-input", position.id)
-                                    min="0.01"
-                                    max="100"
-                                    placeholder="..."
-                                    type="number"
-                                    class="percentage"
-                                    value=if position.target_allocation.is_zero() {
-                                        "".to_string()
-                                    } else {
-                                        (position.target_allocation * dec!(100))
-                                            .round_dp(2)
-                                            .to_string()
-                                    }
-                                    on:input=move |ev| {
-                                        let mut new_positions = positions.get().rows;
-                                        new_positions
+                                            .tolerance
+                                            .relative = relative;
+                                    })
+                            }
+                        />
+                    </td>
+                </tr>
+            </Show>
+            <Show when=move || show_units.get()>
+                <tr class="units">
+                    <td>{t_string!(i18n, units)}</td>
+                    <td class="number">
+                        <input
+                            id=format!("{}-units-input", position.id)
+                            min="0"
+                            max="9999999"
+                            placeholder="..."
+                            type="number"
+                            class:masked=is_private
+                            value=positions
+                                .with_untracked(|value| {
+                                    value
+                                        .rows
+                                        .iter()
+                                        .find(|x| x.id == position.id)
+                                        .and_then(|x| x.units)
+                                        .map(|x| x.normalize().to_string())
+                                        .unwrap_or_default()
+                                })
+                            on:input=move |ev| {
+                                let units = event_target_value(&ev)
+                                    .parse::<Decimal>()
+                                    .ok();
+                                set_positions
+                                    .update(|value| {
+                                        let row = value
+                                            .rows
+                                            .iter_mut()
+                                            .find(|x| x.id == position.id)
+                                            .unwrap();
+                                        row.units = units;
+                                        if let Some(price) = row.unit_price() {
+                                            row.current_position = units
+                                                .unwrap_or_default() * price;
+                                        }
+                                    })
+                            }
+                        />
+                    </td>
+                    <td class="number">
+                        <input
+                            id=format!("{}-price-input", position.id)
+                            min="0"
+                            max="9999999"
+                            placeholder="..."
+                            title=t_string!(i18n, price)
+                            type="number"
+                            class:masked=is_private
+                            value=positions
+                                .with_untracked(|value| {
+                                    value
+                                        .rows
+                                        .iter()
+                                        .find(|x| x.id == position.id)
+                                        .and_then(|x| x.price)
+                                        .map(|x| x.normalize().to_string())
+                                        .unwrap_or_default()
+                                })
+                            on:input=move |ev| {
+                                let price = event_target_value(&ev)
+                                    .parse::<Decimal>()
+                                    .ok();
+                                set_positions
+                                    .update(|value| {
+                                        let row = value
+                                            .rows
+                                            .iter_mut()
+                                            .find(|x| x.id == position.id)
+                                            .unwrap();
+                                        row.price = price;
+                                        if let Some(price) = row.unit_price() {
+                                            row.current_position = row
+                                                .units
+                                                .unwrap_or_default() * price;
+                                        }
+                                    })
+                            }
+                        />
+                    </td>
+                </tr>
+            </Show>
+            <Show when=move || show_currencies.get()>
+                <tr class="currency">
+                    <td>{t_string!(i18n, currency)}</td>
+                    <td class="number">
+                        <select
+                            class="currency-select"
+                            title=t_string!(i18n, currency)
+                            on:change=move |ev| {
+                                let currency = Some(event_target_value(&ev))
+                                    .filter(|x| !x.is_empty());
+                                set_positions
+                                    .update(|value| {
+                                        value
+                                            .rows
                                             .iter_mut()
                                             .find(|x| x.id == position.id)
                                             .unwrap()
-                                            .target_allocation = event_target_value(&ev)
-                                            .parse::<Decimal>()
-                                            .unwrap_or(dec!(0)) / dec!(100);
-                                        set_positions
-                                            .set(PositionsDataStore {
-                                                rows: new_positions,
-                                            })
-                                    }
-                                />
-                            </td>
-                        </tr>
+                                            .currency = currency;
+                                    })
+                            }
+                        >
+                            <option value="">{t_string!(i18n, base_currency)}</option>
+                            {move || {
+                                let current_currency = foreign_currency(position.id);
+                                let base = positions
+                                    .with(|value| value.base_currency.clone().unwrap_or_default());
+                                let mut options = currencies.get();
+                                options.retain(|x| !x.eq_ignore_ascii_case(base.trim()));
+                                // Keep a currency whose exchange rate was removed selectable
+                                if let Some(currency) = current_currency
+                                    .clone()
+                                    .filter(|x| !options.contains(x))
+                                {
+                                    options.push(currency);
+                                }
+                                options
+                                    .into_iter()
+                                    .map(|currency| {
+                                        let selected = current_currency.as_ref() == Some(&currency);
+                                        view! {
+                                            <option value=currency.clone() selected=selected>
+                                                {currency.clone()}
+                                            </option>
+                                        }
+                                    })
+                                    .collect_view()
+                            }}
+                        </select>
+                    </td>
+                    <td />
+                </tr>
+            </Show>
+            <Show when=move || consider_fees.get()>
+                <tr class="fees">
+                    <td>{t_string!(i18n, fees)}</td>
+                    <td class="number">
+                        <input
+                            id=format!("{}-fee-flat-input", position.id)
+                            min="0"
+                            max="9999999"
+                            placeholder=move || {
+                                amount_placeholder(positions.get().fees.flat)
+                            }
+                            title=t_string!(i18n, fee_flat)
+                            type="number"
+                            value=amount_value(position.fees.flat)
+                            on:input=move |ev| {
+                                let flat = parse_amount(&ev);
+                                set_positions
+                                    .update(|value| {
+                                        value
+                                            .rows
+                                            .iter_mut()
+                                            .find(|x| x.id == position.id)
+                                            .unwrap()
+                                            .fees
+                                            .flat = flat;
+                                    })
+                            }
+                        />
+                    </td>
+                    <td class="number">
+                        <input
+                            id=format!("{}-fee-percentage-input", position.id)
+                            min="0"
+                            max="100"
+                            placeholder=move || {
+                                percentage_placeholder(positions.get().fees.percentage)
+                            }
+                            title=t_string!(i18n, fee_percentage)
+                            type="number"
+                            class="percentage"
+                            value=percentage_value(position.fees.percentage)
+                            on:input=move |ev| {
+                                let percentage = parse_percentage(&ev);
+                                set_positions
+                                    .update(|value| {
+                                        value
+                                            .rows
+                                            .iter_mut()
+                                            .find(|x| x.id == position.id)
+                                            .unwrap()
+                                            .fees
+                                            .percentage = percentage;
+                                    })
+                            }
+                        />
+                    </td>
+                </tr>
+                <tr class="minimum-order">
+                    <td>{t_string!(i18n, minimum_order_short)}</td>
+                    <td class="number">
+                        <input
+                            id=format!("{}-fee-minimum-order-input", position.id)
+                            min="0"
+                            max="9999999"
+                            placeholder=move || {
+                                amount_placeholder(positions.get().fees.minimum_order)
+                            }
+                            title=t_string!(i18n, minimum_order)
+                            type="number"
+                            value=amount_value(position.fees.minimum_order)
+                            on:input=move |ev| {
+                                let minimum_order = parse_amount(&ev);
+                                set_positions
+                                    .update(|value| {
+                                        value
+                                            .rows
+                                            .iter_mut()
+                                            .find(|x| x.id == position.id)
+                                            .unwrap()
+                                            .fees
+                                            .minimum_order = minimum_order;
+                                    })
+                            }
+                        />
+                    </td>
+                    <td />
+                </tr>
+            </Show>
+        }
+    };
+
+    let group_rows = move |group_id: Uuid, depth: usize| {
+        let group_of = move || {
+            positions.with(|value| {
+                value
+                    .groups
+                    .iter()
+                    .find(|x| x.id == group_id)
+                    .cloned()
+                    .unwrap_or_default()
+            })
+        };
+        let group = positions.with_untracked(|value| {
+            value
+                .groups
+                .iter()
+                .find(|x| x.id == group_id)
+                .cloned()
+                .unwrap_or_default()
+        });
+        let update_group = move |update: &dyn Fn(&mut AllocationGroup)| {
+            set_positions.update(|value| {
+                if let Some(group) = value.groups.iter_mut().find(|x| x.id == group_id) {
+                    update(group)
+                }
+            })
+        };
+        let group_value = move || positions.with(|value| value.group_value(group_id));
+        let group_target_value = move || {
+            let targets = target_positions()?;
+            positions.with(|value| {
+                Some(
+                    value
+                        .rows
+                        .iter()
+                        .filter(|row| value.is_within_group(row.group, group_id))
+                        .filter_map(|row| targets.iter().find(|x| x.id == row.id))
+                        .map(|x| value.to_base(x.id, x.value))
+                        .sum::<Decimal>(),
+                )
+            })
+        };
+        view! {
+            <tr class="group">
+                <td colspan=3 class="title">
+                    <div class="title-input-container" style:margin-left=indentation(depth)>
+                        <button
+                            class="toggle-group"
+                            title=t_string!(i18n, toggle_group)
+                            on:click=move |_| {
+                                update_view_settings(&|value| value.toggle_collapsed(group_id))
+                            }
+                        >
+                            {move || {
+                                if view_settings.with(|value| value.is_collapsed(group_id)) {
+                                    "▸"
+                                } else {
+                                    "▾"
+                                }
+                            }}
+                        </button>
+                        <input
+                            class="title-input"
+                            type="text"
+                            value=group.name
+                            on:input=move |ev| {
+                                let name = event_target_value(&ev);
+                                update_group(&|group| group.name = name.clone())
+                            }
+                        />
+                        <button
+                            class="add-subgroup"
+                            title=t_string!(i18n, add_subgroup)
+                            on:click=move |_| {
+                                set_positions.update(|value| value.add_group(Some(group_id)))
+                            }
+                        >
+                            <PlusIcon />
+                        </button>
+                        <button
+                            class="remove-position"
+                            title=t_string!(i18n, remove_group)
+                            on:click=move |_| {
+                                on_remove.run(group_of().name);
+                                set_positions.update(|value| value.remove_group(group_id))
+                            }
+                        >
+                            <DeleteIcon />
+                        </button>
+                    </div>
+                </td>
+            </tr>
+            <tr class="current">
+                <td>{t_string!(i18n, current)}</td>
+                <td class="number">
+                    <div class="number">
+                        <Amount>{move || group_value().round_dp(0).to_string()}</Amount>
+                    </div>
+                </td>
+                <td class="number">
+                    <div class="number percentage">
+                        {move || {
+                            positions
+                                .with(|value| value.group_allocation(group_id) * dec!(100))
+                                .round_dp(2)
+                                .to_string()
+                        }}
+                    </div>
+                </td>
+            </tr>
+            <tr class="target">
+                <td>{t_string!(i18n, target)}</td>
+                <td class="number">
+                    <div class="number">
+                        <Amount>
+                            {move || {
+                                group_target_value()
+                                    .unwrap_or(group_value())
+                                    .round_dp(0)
+                                    .to_string()
+                            }}
+                        </Amount>
+                        {move || {
+                            let diff = group_target_value()
+                                .map(|x| (x - group_value()).round_dp(0))
+                                .unwrap_or_default();
+                            view! { <DiffString diff has_braces=true /> }
+                        }}
+                    </div>
+                </td>
+                <td class="number">
+                    <input
+                        id=format!("{}-group-target-input", group_id)
+                        min="0"
+                        max="100"
+                        placeholder="..."
+                        type="number"
+                        class="percentage"
+                        value=percentage_value(Some(group.target_allocation).filter(|x| !x.is_zero()))
+                        on:input=move |ev| {
+                            let target_allocation = parse_percentage(&ev).unwrap_or_default();
+                            update_group(&|group| group.target_allocation = target_allocation)
+                        }
+                    />
+                </td>
+            </tr>
+            <tr class="drift">
+                <td>{t_string!(i18n, drift)}</td>
+                <td class="number">
+                    <div class="number">
+                        {move || {
+                            let drift = positions
+                                .with(|value| {
+                                    value.group_allocation(group_id) - value.group_target(group_id)
+                                });
+                            let diff = (drift * dec!(100)).round_dp(2).normalize();
+                            view! { <DiffString diff has_braces=false unit="%" /> }
+                        }}
+                    </div>
+                </td>
+                <td />
+            </tr>
+            {move || {
+                validation_errors
+                    .get()
+                    .into_iter()
+                    .filter(|error| error.group_id() == Some(group_id))
+                    .map(|error| {
+                        view! {
+                            <tr class="validation">
+                                <td colspan=3>
+                                    <ValidationMessage error />
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()
+            }}
+        }
+    };
+
+    let position_table_rows = {
+        view! {
+            <For
+                each=move || allocation_tree.get()
+                key=|node| *node
+                children=move |node| match node {
+                    AllocationNode::Group { id, depth } => group_rows(id, depth).into_any(),
+                    AllocationNode::Position { id, depth } => {
+                        let position = positions
+                            .with_untracked(|value| {
+                                value.rows.iter().find(|x| x.id == id).cloned().unwrap_or_default()
+                            });
+                        position_rows(position, depth).into_any()
                     }
                 }
             />
@@ -223,21 +1587,15 @@ pub fn Rebalancer() -> impl IntoView {
                     let len = positions.get().rows.len();
                     set_positions
                         .update(|value| {
-                            *value = PositionsDataStore {
-                                rows: value
-                                    .rows
-                                    .iter()
-                                    .cloned()
-                                    .chain([
-                                        PositionInputState {
-                                            id: Uuid::now_v7(),
-                                            name: format!("Position {}", len + 1),
-                                            current_position: dec!(0),
-                                            target_allocation: dec!(0),
-                                        },
-                                    ])
-                                    .collect(),
-                            };
+                            value
+                                .rows
+                                .push(PositionInputState {
+                                    id: Uuid::now_v7(),
+                                    name: format!("Position {}", len + 1),
+                                    current_position: dec!(0),
+                                    target_allocation: dec!(0),
+                                    ..Default::default()
+                                })
                         })
                 }
             >
@@ -247,15 +1605,16 @@ pub fn Rebalancer() -> impl IntoView {
     };
 
     let total_calculation_string = move || {
-        let diff = (position_total()
-            - (target_positions()
-                .iter()
-                .cloned()
-                .fold(dec!(0), |acc, x| acc + x.value.round_dp(0))))
-            * dec!(-1);
-        if strategy.get() == StrategyState::BuySell
-            || !positions.get().is_valid_target_allocation()
-            || !positions.get().all_positions_above_zero()
+        let Some(target_positions) = target_positions() else {
+            return view! { {position_total().to_string()} }.into_any();
+        };
+        let target_total = positions.with(|value| {
+            target_positions.iter().fold(dec!(0), |acc, x| {
+                acc + value.to_base(x.id, x.value).round_dp(0)
+            })
+        });
+        let diff = target_total - position_total();
+        if (strategy.get() == StrategyState::BuySell && !only_outside_tolerance.get())
             || diff == dec!(0)
         {
             view! { {position_total().to_string()} }.into_any()
@@ -264,31 +1623,254 @@ pub fn Rebalancer() -> impl IntoView {
                 {position_total().to_string()}
                 <DiffString diff has_braces=false />
                 {" = ".to_string()}
-                {target_positions()
-                    .iter()
-                    .cloned()
-                    .fold(dec!(0), |acc, x| acc + x.value.round_dp(0))
-                    .to_string()}
+                {target_total.to_string()}
             }
             .into_any()
         }
     };
 
+    let validation_messages = move || {
+        validation_errors
+            .get()
+            .into_iter()
+            .filter(|error| error.position_id().is_none() && error.group_id().is_none())
+            .map(|error| view! { <ValidationMessage error /> })
+            .collect_view()
+    };
+
+    let expected_fees_string = move || {
+        let fees =
+            rebalance.with(|value| value.as_ref().map(|x| x.expected_fees).unwrap_or_default());
+        (consider_fees.get() && !fees.is_zero()).then(|| {
+            view! {
+                <span class="fees">
+                    {format!(
+                        " ({}: {})",
+                        t_string!(i18n, fees),
+                        if is_private.get() {
+                            MASK.to_string()
+                        } else {
+                            fees.round_dp(2).normalize().to_string()
+                        },
+                    )}
+                </span>
+            }
+        })
+    };
+
+    // Trades about to be applied, editable to match what was actually executed
+    let (pending_trades, set_pending_trades) = signal(None::<Vec<Trade>>);
+    let (apply_options, set_apply_options) = signal(ApplyOptions::default());
+
+    let review_trades = move |_| {
+        let strategy = strategy.get_untracked();
+        let positions = positions.get_untracked();
+        spawn_local(async move {
+            let Ok(targets) = engine::compute_targets(strategy, positions.clone()).await else {
+                return;
+            };
+            set_pending_trades.set(Some(
+                positions
+                    .trades_for(&targets)
+                    .into_iter()
+                    .map(|trade| Trade {
+                        amount: trade.amount.round_dp(2),
+                        ..trade
+                    })
+                    .collect(),
+            ));
+        });
+    };
+
+    let pending_trade_rows = move || {
+        pending_trades
+            .get_untracked()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(ix, trade)| {
+                view! {
+                    <tr>
+                        <td>{trade.name}</td>
+                        <td class="number">
+                            <input
+                                id=format!("{}-executed-input", trade.position)
+                                placeholder="0"
+                                title=t_string!(i18n, executed_amount)
+                                type="number"
+                                class:masked=is_private
+                                value=if trade.amount.is_zero() {
+                                    "".to_string()
+                                } else {
+                                    trade.amount.normalize().to_string()
+                                }
+                                on:input=move |ev| {
+                                    let amount = event_target_value(&ev)
+                                        .parse::<Decimal>()
+                                        .unwrap_or_default();
+                                    set_pending_trades
+                                        .update(|value| {
+                                            if let Some(trade) = value
+                                                .as_mut()
+                                                .and_then(|x| x.get_mut(ix))
+                                            {
+                                                trade.amount = amount;
+                                            }
+                                        })
+                                }
+                            />
+                        </td>
+                        <td>{foreign_currency(trade.position)}</td>
+                    </tr>
+                }
+            })
+            .collect_view()
+    };
+
+    let apply_trades = move |_| {
+        if let Some(trades) = pending_trades.get_untracked() {
+            on_apply.run((trades, apply_options.get_untracked()));
+        }
+        set_pending_trades.set(None);
+    };
+
     view! {
-        <main>
+        <>
             <section class="strategy">
                 <b>{t!(i18n, strategy)}:</b>
                 {strategy_options}
             </section>
+            <Show when=move || strategy_amount.get().is_some()>{strategy_amount_input}</Show>
+            {tolerance_options}
+            {units_options}
+            {currency_options}
+            {fee_options}
+            {chart_options}
 
+            {sort_options}
             <table>{position_table_rows}</table>
 
             <section class="add-remove">{add_position_button}</section>
 
+            <section class="add-group">
+                <button
+                    class="add-group"
+                    on:click=move |_| set_positions.update(|value| value.add_group(None))
+                >
+                    <PlusIcon />
+                    {t!(i18n, add_group)}
+                </button>
+            </section>
+
+            <section class="validation">{validation_messages}</section>
+
+            <Show when=move || show_chart.get()>
+                <AllocationComparison positions targets=Signal::derive(target_positions) />
+            </Show>
+
             <section class="total">
                 <b>{t!(i18n, total)}</b>
-                <span>{total_calculation_string}</span>
+                <span>
+                    <Amount>{total_calculation_string}</Amount>
+                    {expected_fees_string}
+                </span>
             </section>
-        </main>
+
+            <section class="apply-rebalance">
+                <button
+                    class="apply-rebalance"
+                    disabled=move || {
+                        !validation_errors.get().is_empty() || pending_trades.with(Option::is_some)
+                    }
+                    on:click=review_trades
+                >
+                    {t!(i18n, apply_rebalance)}
+                </button>
+            </section>
+            <Show when=move || pending_trades.with(Option::is_some)>
+                <section class="pending-trades">
+                    <p>{t!(i18n, apply_rebalance_hint)}</p>
+                    <table>{pending_trade_rows}</table>
+                    <div class="apply-options">
+                        <input
+                            type="checkbox"
+                            id="apply-snapshot"
+                            checked=move || apply_options.get().snapshot
+                            on:change=move |ev| {
+                                let checked = event_target_checked(&ev);
+                                set_apply_options.update(|value| value.snapshot = checked)
+                            }
+                        />
+                        <label for="apply-snapshot">{t!(i18n, apply_snapshot)}</label>
+                        <input
+                            type="checkbox"
+                            id="apply-log"
+                            checked=move || apply_options.get().log
+                            on:change=move |ev| {
+                                let checked = event_target_checked(&ev);
+                                set_apply_options.update(|value| value.log = checked)
+                            }
+                        />
+                        <label for="apply-log">{t!(i18n, apply_log)}</label>
+                    </div>
+                    <div class="apply-actions">
+                        <button class="apply-trades" on:click=apply_trades>
+                            {t!(i18n, apply)}
+                        </button>
+                        <button on:click=move |_| set_pending_trades.set(None)>
+                            {t!(i18n, cancel)}
+                        </button>
+                    </div>
+                </section>
+            </Show>
+        </>
     }
 }
+
+/// Id of the position whose row is at the given viewport coordinates.
+fn position_at(x: i32, y: i32) -> Option<Uuid> {
+    document()
+        .element_from_point(x as f32, y as f32)?
+        .closest("[data-position]")
+        .ok()??
+        .get_attribute("data-position")?
+        .parse()
+        .ok()
+}
+
+fn indentation(depth: usize) -> String {
+    format!("{}rem", depth)
+}
+
+fn percentage_value(value: Option<Decimal>) -> String {
+    value
+        .map(|x| (x * dec!(100)).round_dp(2).normalize().to_string())
+        .unwrap_or_default()
+}
+
+fn percentage_placeholder(value: Option<Decimal>) -> String {
+    value
+        .map(|x| (x * dec!(100)).round_dp(2).normalize().to_string())
+        .unwrap_or("...".to_string())
+}
+
+fn parse_percentage(ev: &leptos::ev::Event) -> Option<Decimal> {
+    event_target_value(ev)
+        .parse::<Decimal>()
+        .ok()
+        .map(|x| x / dec!(100))
+}
+
+fn amount_value(value: Option<Decimal>) -> String {
+    value.map(|x| x.normalize().to_string()).unwrap_or_default()
+}
+
+fn amount_placeholder(value: Option<Decimal>) -> String {
+    value
+        .map(|x| x.normalize().to_string())
+        .unwrap_or("...".to_string())
+}
+
+fn parse_amount(ev: &leptos::ev::Event) -> Option<Decimal> {
+    event_target_value(ev).parse::<Decimal>().ok()
+}
@@ -0,0 +1,609 @@
+use crate::crypto::{CryptoError, EncryptedState, EncryptionKey};
+use crate::tauri;
+use crate::types::{PortfoliosState, PositionsDataStore, StrategyState};
+use codee::{Decoder, Encoder};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+pub const PORTFOLIOS_KEY: &str = "portfolios-state";
+const UNREADABLE_PORTFOLIOS_KEY: &str = "portfolios-state-unreadable";
+const LEGACY_POSITIONS_KEY: &str = "asset-state";
+const LEGACY_STRATEGY_KEY: &str = "strategy-state";
+
+/// Version of the persisted schema written by this build. Every change to the stored format
+/// bumps this number and appends a step to [`MIGRATIONS`].
+///
+/// 0. `asset-state` and `strategy-state` holding a single positions store and strategy
+/// 1. `portfolios-state` holding the portfolios without an envelope
+/// 2. `portfolios-state` wrapped into a [`VersionedState`] envelope
+/// 3. The sort order moved from the positions onto the portfolio
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Migration steps, where the step at index `n` turns data of version `n` into version `n + 1`.
+const MIGRATIONS: [fn(Value) -> Result<Value, StorageError>; SCHEMA_VERSION as usize] =
+    [migrate_v0_to_v1, migrate_v1_to_v2, migrate_v2_to_v3];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VersionedState<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageError {
+    Json(String),
+    UnsupportedVersion(u64),
+    NoPortfolios,
+    Crypto(CryptoError),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Json(message) => write!(f, "invalid stored state: {message}"),
+            StorageError::UnsupportedVersion(version) => write!(
+                f,
+                "stored state has version {version}, but only versions up to {SCHEMA_VERSION} are supported"
+            ),
+            StorageError::NoPortfolios => write!(f, "stored state contains no portfolios"),
+            StorageError::Crypto(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(value: serde_json::Error) -> Self {
+        StorageError::Json(value.to_string())
+    }
+}
+
+impl From<CryptoError> for StorageError {
+    fn from(value: CryptoError) -> Self {
+        StorageError::Crypto(value)
+    }
+}
+
+/// Codec writing the portfolios in the current [`VersionedState`] envelope and migrating any
+/// older format on read.
+pub struct VersionedPortfoliosCodec;
+
+impl Encoder<PortfoliosState> for VersionedPortfoliosCodec {
+    type Error = StorageError;
+    type Encoded = String;
+
+    fn encode(val: &PortfoliosState) -> Result<Self::Encoded, Self::Error> {
+        Ok(serde_json::to_string(&VersionedState {
+            version: SCHEMA_VERSION,
+            data: val,
+        })?)
+    }
+}
+
+impl Decoder<PortfoliosState> for VersionedPortfoliosCodec {
+    type Error = StorageError;
+    type Encoded = str;
+
+    fn decode(val: &Self::Encoded) -> Result<PortfoliosState, Self::Error> {
+        let value: Value = serde_json::from_str(val)?;
+        match value.get("version").and_then(Value::as_u64) {
+            Some(version) => migrate(version, value.get("data").cloned().unwrap_or(Value::Null)),
+            // Version 1 stored the portfolios directly without an envelope.
+            None => migrate(1, value),
+        }
+    }
+}
+
+/// Loading state of the stored portfolios.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageStatus {
+    Loading,
+    /// Encrypted with a passphrase that has not been entered yet.
+    Locked(EncryptedState),
+    Ready,
+    /// Portfolios are stored, but could not be read. Nothing is saved, so they stay as they are.
+    Failed,
+}
+
+/// Stored portfolios as read, before any passphrase is known.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoredState {
+    Plain(PortfoliosState),
+    Encrypted(EncryptedState),
+}
+
+/// Portfolios shared by all views along with how they are stored.
+#[derive(Clone, Copy)]
+pub struct PortfolioStorage {
+    portfolios: RwSignal<PortfoliosState>,
+    status: RwSignal<StorageStatus>,
+    /// Key derived from the passphrase, or `None` if the portfolios are stored in plaintext.
+    key: RwSignal<Option<EncryptionKey>>,
+}
+
+impl PortfolioStorage {
+    pub fn status(&self) -> Signal<StorageStatus> {
+        self.status.into()
+    }
+    pub fn is_encrypted(&self) -> Signal<bool> {
+        let key = self.key;
+        Signal::derive(move || key.with(Option::is_some))
+    }
+    /// Decrypts the stored portfolios. Deriving the key takes a moment on purpose.
+    pub fn unlock(&self, passphrase: &str) -> Result<(), StorageError> {
+        let StorageStatus::Locked(encrypted) = self.status.get_untracked() else {
+            return Ok(());
+        };
+        let key = EncryptionKey::for_state(passphrase, &encrypted)?;
+        let state = VersionedPortfoliosCodec::decode(&key.decrypt(&encrypted)?)?;
+        self.portfolios.set(state);
+        self.key.set(Some(key));
+        self.status.set(StorageStatus::Ready);
+        Ok(())
+    }
+    /// Replaces the locked portfolios with the defaults, for when the passphrase is forgotten.
+    pub fn reset(&self) {
+        self.portfolios.set(PortfoliosState::default());
+        self.key.set(None);
+        self.status.set(StorageStatus::Ready);
+    }
+    /// Sets, changes or removes the passphrase and stores the portfolios again accordingly. The
+    /// `current` passphrase has to match if one is set.
+    pub fn set_passphrase(&self, current: &str, new: Option<&str>) -> Result<(), StorageError> {
+        if let Some(key) = self.key.get_untracked() {
+            if !key.matches(current)? {
+                return Err(CryptoError::WrongPassphrase.into());
+            }
+        }
+        let key = new.map(EncryptionKey::new).transpose()?;
+        let is_encrypted = key.is_some();
+        self.key.set(key);
+        // The backup of the previous save is replaced as well, so it holds no copy with the old
+        // key or in plaintext
+        self.save(true);
+        if is_encrypted {
+            remove_plaintext_copies();
+        }
+        Ok(())
+    }
+    fn save(&self, replace_backup: bool) {
+        let state = self.portfolios.get_untracked();
+        let key = self.key.get_untracked();
+        match encode_stored(&state, key.as_ref()) {
+            Ok(contents) => write_stored(contents, replace_backup),
+            Err(error) => log::error!("{error}"),
+        }
+    }
+}
+
+/// Loads the portfolios and provides them to all views. The browser keeps them in local storage.
+/// On Tauri they are stored as file in the app data directory, which is read asynchronously.
+/// Either way, they are only available once the status is [`StorageStatus::Ready`].
+pub fn provide_portfolios() -> PortfolioStorage {
+    let storage = PortfolioStorage {
+        portfolios: RwSignal::new(PortfoliosState::default()),
+        status: RwSignal::new(StorageStatus::Loading),
+        key: RwSignal::new(None),
+    };
+    provide_context(storage);
+    let on_loaded = move |stored: Result<Option<StoredState>, String>| match stored {
+        Ok(Some(StoredState::Encrypted(encrypted))) => {
+            storage.status.set(StorageStatus::Locked(encrypted))
+        }
+        Ok(Some(StoredState::Plain(state))) => {
+            storage.portfolios.set(state);
+            storage.status.set(StorageStatus::Ready);
+        }
+        Ok(None) => storage.status.set(StorageStatus::Ready),
+        Err(error) => {
+            log::error!("{error}");
+            storage.status.set(StorageStatus::Failed);
+        }
+    };
+    if tauri::is_tauri() {
+        spawn_local(async move { on_loaded(load_file_state().await) });
+    } else {
+        on_loaded(Ok(read_local_storage()));
+    }
+    // Every change is saved once loaded, which also writes the state imported from local
+    // storage. Changing the passphrase re-encrypts the portfolios.
+    Effect::new(move |_| {
+        storage.portfolios.track();
+        storage.key.track();
+        if storage.status.get() == StorageStatus::Ready {
+            storage.save(false);
+        }
+    });
+    storage
+}
+
+pub fn use_portfolio_storage() -> PortfolioStorage {
+    expect_context::<PortfolioStorage>()
+}
+
+pub fn use_portfolios() -> (Signal<PortfoliosState>, WriteSignal<PortfoliosState>) {
+    let (portfolios, set_portfolios) = use_portfolio_storage().portfolios.split();
+    (portfolios.into(), set_portfolios)
+}
+
+/// Encodes the portfolios and encrypts them if there is a key.
+pub fn encode_stored(
+    state: &PortfoliosState,
+    key: Option<&EncryptionKey>,
+) -> Result<String, StorageError> {
+    let encoded = VersionedPortfoliosCodec::encode(state)?;
+    match key {
+        Some(key) => Ok(serde_json::to_string(&key.encrypt(&encoded)?)?),
+        None => Ok(encoded),
+    }
+}
+
+/// Reads a stored value, which is either encrypted or in any supported schema version.
+pub fn decode_stored(stored: &str) -> Result<StoredState, StorageError> {
+    match EncryptedState::parse(stored) {
+        Some(encrypted) => Ok(StoredState::Encrypted(encrypted)),
+        None => VersionedPortfoliosCodec::decode(stored).map(StoredState::Plain),
+    }
+}
+
+thread_local! {
+    // Latest portfolios waiting to be written to the state file along with whether they replace
+    // the backup, and whether a write is running
+    static PENDING_SAVE: RefCell<Option<(String, bool)>> = const { RefCell::new(None) };
+    static IS_SAVING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Writes the encoded portfolios. On Tauri, `replace_backup` writes the backup file with them as
+/// well instead of keeping the previous portfolios in it.
+fn write_stored(contents: String, replace_backup: bool) {
+    if tauri::is_tauri() {
+        // Files are written one at a time, so an earlier save never overwrites a later one.
+        // Changes made in the meantime are written together afterwards.
+        PENDING_SAVE.with_borrow_mut(|pending| {
+            let replace_backup = replace_backup || pending.as_ref().is_some_and(|x| x.1);
+            *pending = Some((contents, replace_backup));
+        });
+        if IS_SAVING.replace(true) {
+            return;
+        }
+        spawn_local(async move {
+            while let Some((contents, replace_backup)) = PENDING_SAVE.take() {
+                if let Err(error) = tauri::save_portfolios(&contents, replace_backup).await {
+                    log::error!("{error}");
+                }
+            }
+            IS_SAVING.set(false);
+        });
+    } else if let Some(storage) = window().local_storage().ok().flatten() {
+        if storage.set_item(PORTFOLIOS_KEY, &contents).is_err() {
+            log::error!("cannot write to local storage");
+        }
+    }
+}
+
+/// Removes values from local storage that may hold portfolios in plaintext once they are
+/// encrypted. On Tauri this includes the portfolios imported into the state file. Portfolios
+/// that could not be read are kept, as they may be the only copy left.
+fn remove_plaintext_copies() {
+    let Some(storage) = window().local_storage().ok().flatten() else {
+        return;
+    };
+    let mut keys = vec![LEGACY_POSITIONS_KEY, LEGACY_STRATEGY_KEY];
+    if tauri::is_tauri() {
+        keys.push(PORTFOLIOS_KEY);
+    }
+    for key in keys {
+        let _ = storage.remove_item(key);
+    }
+}
+
+/// Reads the state file, or its backup if the state file cannot be decoded. Without a state
+/// file, the portfolios are imported from the local storage used before. Fails if there are
+/// stored portfolios but none can be read.
+async fn load_file_state() -> Result<Option<StoredState>, String> {
+    let Some(stored) = tauri::load_portfolios(false).await? else {
+        return Ok(read_local_storage());
+    };
+    let error = match decode_stored(&stored) {
+        Ok(state) => return Ok(Some(state)),
+        Err(error) => error,
+    };
+    log::error!("{error}");
+    // Kept aside, as the state file is replaced with the next save
+    keep_unreadable(&stored);
+    // Only read now, so a backup that cannot be read never gets in the way of a good state file
+    let backup = tauri::load_portfolios(true).await?;
+    decode_backup(backup.as_deref(), error).map(Some)
+}
+
+/// Decodes the backup of a state file that could not be decoded with `error`.
+fn decode_backup(backup: Option<&str>, error: StorageError) -> Result<StoredState, String> {
+    let backup = backup.ok_or(error.to_string())?;
+    decode_stored(backup).map_err(|error| error.to_string())
+}
+
+/// Copies stored portfolios that cannot be read to a separate key in local storage, so they are
+/// not lost once other portfolios are saved.
+fn keep_unreadable(stored: &str) {
+    if let Some(storage) = window().local_storage().ok().flatten() {
+        let _ = storage.set_item(UNREADABLE_PORTFOLIOS_KEY, stored);
+    }
+}
+
+/// Returns the portfolios kept in local storage, migrating legacy values if needed. Stored
+/// portfolios that cannot be read are copied to a separate key, so they are not lost once the
+/// defaults are written back.
+fn read_local_storage() -> Option<StoredState> {
+    let storage = window().local_storage().ok().flatten()?;
+    let Some(stored) = storage.get_item(PORTFOLIOS_KEY).ok().flatten() else {
+        return migrate_legacy_state(&storage).map(StoredState::Plain);
+    };
+    decode_stored(&stored)
+        .inspect_err(|error| {
+            log::error!("{error}");
+            keep_unreadable(&stored);
+        })
+        .ok()
+}
+
+/// Runs the migration chain on `data` stored with schema `version` and decodes the result.
+pub fn migrate(version: u64, mut data: Value) -> Result<PortfoliosState, StorageError> {
+    if version > SCHEMA_VERSION as u64 {
+        return Err(StorageError::UnsupportedVersion(version));
+    }
+    for step in &MIGRATIONS[version as usize..] {
+        data = step(data)?;
+    }
+    let state: PortfoliosState = serde_json::from_value(data)?;
+    if state.portfolios.is_empty() {
+        return Err(StorageError::NoPortfolios);
+    }
+    Ok(state)
+}
+
+/// Version 0 data is an object holding the raw values of the legacy keys, either of which may be
+/// missing.
+fn migrate_v0_to_v1(data: Value) -> Result<Value, StorageError> {
+    let positions = match data.get(LEGACY_POSITIONS_KEY) {
+        Some(value) if !value.is_null() => {
+            Some(serde_json::from_value::<PositionsDataStore>(value.clone())?)
+        }
+        _ => None,
+    };
+    let strategy = match data.get(LEGACY_STRATEGY_KEY) {
+        Some(value) if !value.is_null() => {
+            Some(serde_json::from_value::<StrategyState>(value.clone())?)
+        }
+        _ => None,
+    };
+    Ok(serde_json::to_value(PortfoliosState::from_legacy(
+        positions, strategy,
+    ))?)
+}
+
+/// Only the envelope was added, the data itself is unchanged.
+fn migrate_v1_to_v2(data: Value) -> Result<Value, StorageError> {
+    Ok(data)
+}
+
+/// Portfolios without a sort order in their positions keep the default one.
+fn migrate_v2_to_v3(mut data: Value) -> Result<Value, StorageError> {
+    let portfolios = data
+        .get_mut("portfolios")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for portfolio in portfolios {
+        let sort = portfolio
+            .get_mut("positions")
+            .and_then(Value::as_object_mut)
+            .and_then(|positions| positions.remove("sort"));
+        if let (Some(sort), Some(portfolio)) = (sort, portfolio.as_object_mut()) {
+            portfolio.insert("sort".to_string(), sort);
+        }
+    }
+    Ok(data)
+}
+
+/// Migrates the legacy values, which are left in place, as long as no portfolios have been
+/// stored yet.
+fn migrate_legacy_state(storage: &web_sys::Storage) -> Option<PortfoliosState> {
+    let read_legacy = |key| {
+        storage
+            .get_item(key)
+            .ok()
+            .flatten()
+            .and_then(|x| serde_json::from_str::<Value>(&x).ok())
+    };
+    let positions = read_legacy(LEGACY_POSITIONS_KEY);
+    let strategy = read_legacy(LEGACY_STRATEGY_KEY);
+    if positions.is_none() && strategy.is_none() {
+        return None;
+    }
+    let legacy = json!({ LEGACY_POSITIONS_KEY: positions, LEGACY_STRATEGY_KEY: strategy });
+    migrate(0, legacy)
+        .inspect_err(|error| log::error!("{error}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::tests::TEST_PARAMS;
+    use crate::types::PositionSort;
+    use rust_decimal_macros::dec;
+
+    const V0_POSITIONS: &str = r#"{"rows":[
+        {"id":"0199a9a0-0000-7000-8000-000000000001","name":"Stocks","current_position":"700","target_allocation":"0.6"},
+        {"id":"0199a9a0-0000-7000-8000-000000000002","name":"Bonds","current_position":"300","target_allocation":"0.4"}
+    ]}"#;
+
+    const V1_PORTFOLIOS: &str = r#"{"portfolios":[{
+        "id":"0199a9a0-0000-7000-8000-0000000000a1",
+        "name":"Retirement",
+        "positions":{"rows":[
+            {"id":"0199a9a0-0000-7000-8000-000000000001","name":"Stocks","current_position":"700","target_allocation":"0.6",
+             "tolerance":{"absolute":"0.02","relative":null},"units":"7","price":"100",
+             "fees":{"flat":"1","percentage":null,"minimum_order":null}},
+            {"id":"0199a9a0-0000-7000-8000-000000000002","name":"Bonds","current_position":"300","target_allocation":"0.4"}
+        ],
+        "tolerance":{"absolute":"0.05","relative":"0.25"},"only_outside_tolerance":true},
+        "strategy":{"Contribute":"500"}
+    }],"active":"0199a9a0-0000-7000-8000-0000000000a1"}"#;
+
+    #[test]
+    fn load_v0_legacy_state() {
+        // Arrange
+        let legacy = json!({
+            LEGACY_POSITIONS_KEY: serde_json::from_str::<Value>(V0_POSITIONS).unwrap(),
+            LEGACY_STRATEGY_KEY: "BuySell",
+        });
+
+        // Act
+        let state = migrate(0, legacy).unwrap();
+
+        // Assert
+        assert_eq!(state.portfolios.len(), 1);
+        let portfolio = state.active();
+        assert_eq!(portfolio.strategy, StrategyState::BuySell);
+        assert_eq!(portfolio.positions.rows.len(), 2);
+        assert_eq!(portfolio.positions.rows[0].name, "Stocks");
+        assert_eq!(portfolio.positions.rows[0].current_position, dec!(700));
+        assert_eq!(portfolio.positions.rows[1].target_allocation, dec!(0.4));
+        assert_eq!(portfolio.positions.rows[1].units, None);
+    }
+
+    #[test]
+    fn load_v0_legacy_strategy_only() {
+        // Arrange
+        let legacy = json!({ LEGACY_POSITIONS_KEY: null, LEGACY_STRATEGY_KEY: "Sell" });
+
+        // Act
+        let state = migrate(0, legacy).unwrap();
+
+        // Assert
+        assert_eq!(state.active().strategy, StrategyState::Sell);
+        assert_eq!(
+            state.active().positions.rows.len(),
+            PositionsDataStore::default().rows.len()
+        );
+    }
+
+    #[test]
+    fn load_v1_unversioned_portfolios() {
+        // Act
+        let state = VersionedPortfoliosCodec::decode(V1_PORTFOLIOS).unwrap();
+
+        // Assert
+        let portfolio = state.active();
+        assert_eq!(portfolio.name, "Retirement");
+        assert_eq!(portfolio.strategy, StrategyState::Contribute(dec!(500)));
+        assert!(portfolio.positions.only_outside_tolerance);
+        assert!(!portfolio.positions.consider_fees);
+        assert_eq!(portfolio.positions.rows[0].units, Some(dec!(7)));
+        assert_eq!(portfolio.positions.rows[0].fees.flat, Some(dec!(1)));
+        assert_eq!(portfolio.positions.rows[1].price, None);
+    }
+
+    #[test]
+    fn load_v2_roundtrip() {
+        // Arrange
+        let state = VersionedPortfoliosCodec::decode(V1_PORTFOLIOS).unwrap();
+
+        // Act
+        let encoded = VersionedPortfoliosCodec::encode(&state).unwrap();
+        let decoded = VersionedPortfoliosCodec::decode(&encoded).unwrap();
+
+        // Assert
+        let envelope: Value = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(envelope["version"], json!(SCHEMA_VERSION));
+        assert_eq!(decoded, state);
+    }
+
+    #[test]
+    fn load_v2_moves_sort_onto_portfolio() {
+        // Arrange
+        let mut data: Value = serde_json::from_str(V1_PORTFOLIOS).unwrap();
+        data["portfolios"][0]["positions"]["sort"] = json!("Drift");
+
+        // Act
+        let state = migrate(2, data).unwrap();
+
+        // Assert
+        assert_eq!(state.active().sort, PositionSort::Drift);
+    }
+
+    #[test]
+    fn decode_backup_fails_without_readable_backup() {
+        // Arrange
+        let backup = VersionedPortfoliosCodec::encode(
+            &VersionedPortfoliosCodec::decode(V1_PORTFOLIOS).unwrap(),
+        )
+        .unwrap();
+        let error = decode_stored(r#"{"version":2,"da"#).unwrap_err();
+
+        // Act
+        let restored = decode_backup(Some(&backup), error.clone());
+        let missing = decode_backup(None, error.clone());
+        let unreadable = decode_backup(Some("{}"), error.clone());
+
+        // Assert
+        assert!(matches!(
+            restored,
+            Ok(StoredState::Plain(state)) if state.active().name == "Retirement"
+        ));
+        assert_eq!(missing, Err(error.to_string()));
+        assert!(unreadable.is_err());
+    }
+
+    #[test]
+    fn encode_stored_encrypts_with_key() {
+        // Arrange
+        let state = VersionedPortfoliosCodec::decode(V1_PORTFOLIOS).unwrap();
+        let key = EncryptionKey::with_params("correct horse", TEST_PARAMS).unwrap();
+
+        // Act
+        let encrypted = encode_stored(&state, Some(&key)).unwrap();
+        let plain = encode_stored(&state, None).unwrap();
+
+        // Assert
+        assert!(!encrypted.contains("Retirement"));
+        let Ok(StoredState::Encrypted(stored)) = decode_stored(&encrypted) else {
+            panic!("expected an encrypted state");
+        };
+        let decrypted = key.decrypt(&stored).unwrap();
+        assert_eq!(decrypted, plain);
+        assert_eq!(decode_stored(&plain), Ok(StoredState::Plain(state)));
+    }
+
+    #[test]
+    fn load_newer_version_fails() {
+        // Arrange
+        let stored = format!(r#"{{"version":{},"data":{{}}}}"#, SCHEMA_VERSION + 1);
+
+        // Act
+        let result = VersionedPortfoliosCodec::decode(&stored);
+
+        // Assert
+        assert_eq!(
+            result,
+            Err(StorageError::UnsupportedVersion(SCHEMA_VERSION as u64 + 1))
+        );
+    }
+
+    #[test]
+    fn load_without_portfolios_fails() {
+        // Act
+        let result = VersionedPortfoliosCodec::decode(
+            r#"{"version":2,"data":{"portfolios":[],"active":"0199a9a0-0000-7000-8000-0000000000a1"}}"#,
+        );
+
+        // Assert
+        assert_eq!(result, Err(StorageError::NoPortfolios));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::types::{Portfolio, PositionsDataStore, RebalanceError, StrategyState, TargetPosition};
+use leptos::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use web_sys::js_sys::{Reflect, JSON};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], js_name = invoke, catch)]
+    async fn tauri_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+}
+
+/// Whether the frontend runs inside the Tauri webview, which exposes its API as global.
+pub fn is_tauri() -> bool {
+    Reflect::has(&window(), &JsValue::from_str("__TAURI__")).unwrap_or(false)
+}
+
+/// Calls a Tauri command, passing the arguments and result through JSON.
+pub async fn invoke<A: Serialize, R: DeserializeOwned>(cmd: &str, args: &A) -> Result<R, String> {
+    let args = to_js(args)?;
+    let result = tauri_invoke(cmd, args).await.map_err(js_error)?;
+    from_js(&result)
+}
+
+/// Calls a Tauri command returning a `Result`, decoding its error as `E` instead of a message.
+/// The outer error is returned if the command could not be called at all.
+pub async fn invoke_result<A: Serialize, R: DeserializeOwned, E: DeserializeOwned>(
+    cmd: &str,
+    args: &A,
+) -> Result<Result<R, E>, String> {
+    let args = to_js(args)?;
+    match tauri_invoke(cmd, args).await {
+        Ok(result) => from_js(&result).map(Ok),
+        Err(error) => from_js(&error).map(Err).map_err(|_| js_error(error)),
+    }
+}
+
+fn to_js<A: Serialize>(value: &A) -> Result<JsValue, String> {
+    let value = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    JSON::parse(&value).map_err(js_error)
+}
+
+fn from_js<R: DeserializeOwned>(value: &JsValue) -> Result<R, String> {
+    let value = JSON::stringify(value)
+        .ok()
+        .and_then(|x| x.as_string())
+        .unwrap_or("null".to_string());
+    serde_json::from_str(&value).map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveTextFileArgs<'a> {
+    pub file_name: &'a str,
+    pub extension: &'a str,
+    pub contents: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenTextFileArgs<'a> {
+    pub extension: &'a str,
+}
+
+/// Lets the user pick a location with the native save dialog and writes the file there. Returns
+/// `false` if the dialog was cancelled.
+pub async fn save_text_file(
+    file_name: &str,
+    extension: &str,
+    contents: &str,
+) -> Result<bool, String> {
+    invoke(
+        "save_text_file",
+        &SaveTextFileArgs {
+            file_name,
+            extension,
+            contents,
+        },
+    )
+    .await
+}
+
+/// Lets the user pick a file with the native open dialog and returns its contents, or `None` if
+/// the dialog was cancelled.
+pub async fn open_text_file(extension: &str) -> Result<Option<String>, String> {
+    invoke("open_text_file", &OpenTextFileArgs { extension }).await
+}
+
+#[derive(Serialize)]
+pub struct PortfolioArgs<'a> {
+    pub strategy: StrategyState,
+    pub positions: &'a PositionsDataStore,
+}
+
+#[derive(Serialize)]
+pub struct SavePortfolioFileArgs<'a> {
+    pub portfolio: &'a Portfolio,
+}
+
+pub async fn compute_targets(
+    strategy: StrategyState,
+    positions: &PositionsDataStore,
+) -> Result<Result<Vec<TargetPosition>, RebalanceError>, String> {
+    invoke_result(
+        "compute_targets",
+        &PortfolioArgs {
+            strategy,
+            positions,
+        },
+    )
+    .await
+}
+
+pub async fn validate_portfolio(
+    strategy: StrategyState,
+    positions: &PositionsDataStore,
+) -> Result<Vec<RebalanceError>, String> {
+    invoke(
+        "validate_portfolio",
+        &PortfolioArgs {
+            strategy,
+            positions,
+        },
+    )
+    .await
+}
+
+/// Lets the user pick a location with the native save dialog and writes the portfolio there.
+/// Returns `false` if the dialog was cancelled.
+pub async fn save_portfolio_file(portfolio: &Portfolio) -> Result<bool, String> {
+    invoke("save_portfolio_file", &SavePortfolioFileArgs { portfolio }).await
+}
+
+/// Lets the user pick a portfolio file with the native open dialog and returns the portfolio, or
+/// `None` if the dialog was cancelled.
+pub async fn open_portfolio_file() -> Result<Option<Portfolio>, String> {
+    invoke("open_portfolio_file", &serde_json::Map::new()).await
+}
+
+#[derive(Serialize)]
+pub struct LoadPortfoliosArgs {
+    pub backup: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavePortfoliosArgs<'a> {
+    pub contents: &'a str,
+    pub replace_backup: bool,
+}
+
+/// Reads the portfolios stored in the app data directory, or those of the previous save if
+/// `backup` is set. Returns `None` if nothing has been stored yet.
+pub async fn load_portfolios(backup: bool) -> Result<Option<String>, String> {
+    invoke("load_portfolios", &LoadPortfoliosArgs { backup }).await
+}
+
+/// Stores the portfolios in the app data directory. The backup is replaced with them as well if
+/// `replace_backup` is set, instead of keeping the previous portfolios.
+pub async fn save_portfolios(contents: &str, replace_backup: bool) -> Result<(), String> {
+    invoke(
+        "save_portfolios",
+        &SavePortfoliosArgs {
+            contents,
+            replace_backup,
+        },
+    )
+    .await
+}
+
+fn js_error(error: JsValue) -> String {
+    error.as_string().unwrap_or_else(|| format!("{:?}", error))
+}
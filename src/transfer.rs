@@ -0,0 +1,502 @@
+use crate::backup::{self, Backup, BackupError, RestoreMode, BACKUP_FILE_NAME};
+use crate::components::*;
+use crate::engine;
+use crate::i18n::*;
+use crate::privacy::Amount;
+use crate::storage;
+use crate::tauri;
+use crate::types::{Portfolio, PortfoliosState, RebalanceError};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use rebalax_core::csv::{self, CsvPreview, ImportedPosition};
+use rust_decimal_macros::dec;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, HtmlInputElement, Url};
+
+#[component]
+pub fn CsvTransfer(
+    portfolios: Signal<PortfoliosState>,
+    #[prop(into)] on_import: Callback<Vec<ImportedPosition>>,
+) -> impl IntoView {
+    let i18n = use_i18n();
+    let (preview, set_preview) = signal(None::<CsvPreview>);
+
+    let export_csv = move |_| {
+        let portfolio = portfolios.get_untracked().active().clone();
+        let decimal_separator = decimal_separator(i18n.get_locale_untracked());
+        spawn_local(async move {
+            let targets = engine::compute_targets(portfolio.strategy, portfolio.positions.clone())
+                .await
+                .ok();
+            let content =
+                csv::export_positions(&portfolio.positions, targets.as_deref(), decimal_separator);
+            save_file(
+                format!("{}.csv", portfolio.name),
+                "csv",
+                "text/csv",
+                content,
+            );
+        });
+    };
+
+    let read_csv = move |ev: leptos::ev::Event| {
+        read_selected_file(&ev, move |text| {
+            let decimal_separator = decimal_separator(i18n.get_locale_untracked());
+            set_preview.set(Some(csv::parse_positions(&text, decimal_separator)))
+        });
+    };
+
+    let is_existing = move |name: &str| {
+        portfolios.with(|value| {
+            value
+                .active()
+                .positions
+                .rows
+                .iter()
+                .any(|x| x.name.trim().to_lowercase() == name.to_lowercase())
+        })
+    };
+
+    let preview_view = move || {
+        let preview = preview.get()?;
+        let has_positions = !preview.positions.is_empty();
+        let imported = preview.positions.clone();
+        Some(view! {
+            <section class="csv-preview">
+                <table>
+                    {preview
+                        .positions
+                        .into_iter()
+                        .map(|position| {
+                            let current_position = position
+                                .current_position
+                                .round_dp(2)
+                                .normalize()
+                                .to_string();
+                            let status = if is_existing(&position.name) {
+                                t_string!(i18n, csv_update)
+                            } else {
+                                t_string!(i18n, csv_new)
+                            };
+                            view! {
+                                <tr>
+                                    <td>{position.name}</td>
+                                    <td class="number">
+                                        <Amount>{current_position.clone()}</Amount>
+                                    </td>
+                                    <td class="number percentage">
+                                        {(position.target_allocation * dec!(100))
+                                            .round_dp(2)
+                                            .normalize()
+                                            .to_string()}
+                                    </td>
+                                    <td class="csv-status">{status}</td>
+                                </tr>
+                            }
+                        })
+                        .collect_view()}
+                </table>
+                {preview
+                    .errors
+                    .into_iter()
+                    .map(|error| view! { <CsvErrorMessage error /> })
+                    .collect_view()}
+                <div class="csv-actions">
+                    <button
+                        class="apply-import"
+                        disabled=!has_positions
+                        on:click=move |_| {
+                            on_import.run(imported.clone());
+                            set_preview.set(None);
+                        }
+                    >
+                        {t!(i18n, csv_apply_import)}
+                    </button>
+                    <button class="cancel-import" on:click=move |_| set_preview.set(None)>
+                        {t!(i18n, cancel)}
+                    </button>
+                </div>
+            </section>
+        })
+    };
+
+    view! {
+        <section class="transfer">
+            <button class="export-csv" title=t_string!(i18n, csv_export) on:click=export_csv>
+                <DownloadIcon />
+                {t!(i18n, csv_export)}
+            </button>
+            <label class="import-csv button" title=t_string!(i18n, csv_import)>
+                <UploadIcon />
+                {t!(i18n, csv_import)}
+                <input type="file" accept=".csv,text/csv,text/plain" on:change=read_csv />
+            </label>
+        </section>
+        {preview_view}
+    }
+}
+
+/// Outcome of reading a portfolio file: the name of the imported portfolio and everything that
+/// keeps it from being rebalanced, or that the file could not be read.
+type PortfolioImport = Result<(String, Vec<RebalanceError>), String>;
+
+#[component]
+pub fn PortfolioTransfer(
+    portfolios: Signal<PortfoliosState>,
+    #[prop(into)] on_import: Callback<Portfolio>,
+) -> impl IntoView {
+    let i18n = use_i18n();
+    let (imported, set_imported) = signal(None::<PortfolioImport>);
+
+    let import_portfolio = move |portfolio: Portfolio| {
+        spawn_local(async move {
+            let errors =
+                engine::validate_portfolio(portfolio.strategy, portfolio.positions.clone()).await;
+            set_imported.set(Some(Ok((portfolio.name.clone(), errors))));
+            on_import.run(portfolio);
+        });
+    };
+
+    let export_portfolio = move |_| {
+        let portfolio = portfolios.get_untracked().active().clone();
+        if tauri::is_tauri() {
+            spawn_local(async move {
+                if let Err(error) = tauri::save_portfolio_file(&portfolio).await {
+                    log::error!("{error}");
+                }
+            });
+        } else {
+            match serde_json::to_string_pretty(&portfolio) {
+                Ok(content) => download_file(
+                    &format!("{}.json", portfolio.name),
+                    "application/json",
+                    &content,
+                ),
+                Err(error) => log::error!("{error}"),
+            }
+        }
+    };
+
+    let open_portfolio = move |_| {
+        spawn_local(async move {
+            match tauri::open_portfolio_file().await {
+                Ok(Some(portfolio)) => import_portfolio(portfolio),
+                Ok(None) => {}
+                Err(error) => {
+                    log::error!("{error}");
+                    set_imported.set(Some(Err(error)));
+                }
+            }
+        })
+    };
+
+    let read_portfolio = move |text: String| match serde_json::from_str::<Portfolio>(&text) {
+        Ok(portfolio) => import_portfolio(portfolio),
+        Err(error) => {
+            log::error!("{error}");
+            set_imported.set(Some(Err(error.to_string())));
+        }
+    };
+
+    let import_button = move || {
+        if tauri::is_tauri() {
+            view! {
+                <button class="import-portfolio" on:click=open_portfolio>
+                    <UploadIcon />
+                    {t!(i18n, portfolio_import)}
+                </button>
+            }
+            .into_any()
+        } else {
+            view! {
+                <label class="import-portfolio button">
+                    <UploadIcon />
+                    {t!(i18n, portfolio_import)}
+                    <input
+                        type="file"
+                        accept=".json,application/json"
+                        on:change=move |ev| read_selected_file(&ev, read_portfolio)
+                    />
+                </label>
+            }
+            .into_any()
+        }
+    };
+
+    let import_result = move || {
+        let result = match imported.get()? {
+            Ok((name, errors)) => {
+                let issues = (!errors.is_empty())
+                    .then(|| view! { <p>{t!(i18n, portfolio_import_issues)}</p> });
+                view! {
+                    <p>{t_string!(i18n, portfolio_imported, name = name)}</p>
+                    {issues}
+                    {errors
+                        .into_iter()
+                        .map(|error| view! { <ValidationMessage error /> })
+                        .collect_view()}
+                }
+                .into_any()
+            }
+            Err(_) => view! {
+                <span class="validation-message">{t!(i18n, portfolio_import_error)}</span>
+            }
+            .into_any(),
+        };
+        Some(view! {
+            <section class="portfolio-import">
+                {result}
+                <button on:click=move |_| set_imported.set(None)>{t!(i18n, close)}</button>
+            </section>
+        })
+    };
+
+    view! {
+        <section class="transfer">
+            <button class="export-portfolio" on:click=export_portfolio>
+                <DownloadIcon />
+                {t!(i18n, portfolio_export)}
+            </button>
+            {import_button}
+        </section>
+        {import_result}
+    }
+}
+
+#[component]
+pub fn BackupTransfer() -> impl IntoView {
+    let i18n = use_i18n();
+    let (portfolios, set_portfolios) = storage::use_portfolios();
+    let (pending, set_pending) = signal(None::<Result<Backup, BackupError>>);
+    let (mode, set_mode) = signal(RestoreMode::default());
+    let (restored, set_restored) = signal(false);
+
+    let read_backup = move |text: String| {
+        set_restored.set(false);
+        set_pending.set(Some(backup::parse_backup(&text)));
+    };
+
+    let create_backup = move |_| match backup::create_backup(
+        &portfolios.get_untracked(),
+        i18n.get_locale_untracked(),
+    ) {
+        Ok(content) => save_file(
+            BACKUP_FILE_NAME.to_string(),
+            "json",
+            "application/json",
+            content,
+        ),
+        Err(error) => log::error!("{error}"),
+    };
+
+    let open_backup = move |_| {
+        spawn_local(async move {
+            match tauri::open_text_file("json").await {
+                Ok(Some(text)) => read_backup(text),
+                Ok(None) => {}
+                Err(error) => set_pending.set(Some(Err(BackupError::Read(error)))),
+            }
+        })
+    };
+
+    let restore_button = move || {
+        if tauri::is_tauri() {
+            view! {
+                <button class="restore-backup" on:click=open_backup>
+                    <UploadIcon />
+                    {t!(i18n, restore_backup)}
+                </button>
+            }
+            .into_any()
+        } else {
+            view! {
+                <label class="restore-backup button">
+                    <UploadIcon />
+                    {t!(i18n, restore_backup)}
+                    <input
+                        type="file"
+                        accept=".json,application/json"
+                        on:change=move |ev| read_selected_file(&ev, read_backup)
+                    />
+                </label>
+            }
+            .into_any()
+        }
+    };
+
+    let affected_portfolios = move |label: String, names: Vec<String>| {
+        (!names.is_empty()).then(|| {
+            view! {
+                <p>
+                    <b>{label}:</b>
+                    {format!(" {}", names.join(", "))}
+                </p>
+            }
+        })
+    };
+
+    let restore_preview = move || {
+        let backup = match pending.get()? {
+            Ok(backup) => backup,
+            Err(error) => {
+                return Some(
+                    view! {
+                        <section class="restore-preview">
+                            <BackupErrorMessage error />
+                            <div class="restore-actions">
+                                <button on:click=move |_| set_pending.set(None)>
+                                    {t!(i18n, cancel)}
+                                </button>
+                            </div>
+                        </section>
+                    }
+                    .into_any(),
+                );
+            }
+        };
+        let preview = backup::preview_restore(&portfolios.get(), &backup.portfolios, mode.get());
+        let locale = backup.locale.filter(|x| *x != i18n.get_locale()).map(|x| {
+            let name = match x {
+                Locale::en => "English",
+                Locale::de => "Deutsch",
+            };
+            affected_portfolios(
+                t_string!(i18n, language).to_string(),
+                vec![name.to_string()],
+            )
+        });
+        Some(
+            view! {
+                <section class="restore-preview">
+                    <div class="restore-modes">
+                        <input
+                            type="radio"
+                            name="restore-mode"
+                            id="restore-mode-merge"
+                            checked=mode.get() == RestoreMode::Merge
+                            on:change=move |_| set_mode.set(RestoreMode::Merge)
+                        />
+                        <label for="restore-mode-merge">{t!(i18n, restore_merge)}</label>
+                        <input
+                            type="radio"
+                            name="restore-mode"
+                            id="restore-mode-overwrite"
+                            checked=mode.get() == RestoreMode::Overwrite
+                            on:change=move |_| set_mode.set(RestoreMode::Overwrite)
+                        />
+                        <label for="restore-mode-overwrite">{t!(i18n, restore_overwrite)}</label>
+                    </div>
+                    {affected_portfolios(t_string!(i18n, restore_added).to_string(), preview.added)}
+                    {affected_portfolios(
+                        t_string!(i18n, restore_replaced).to_string(),
+                        preview.replaced,
+                    )}
+                    {affected_portfolios(t_string!(i18n, restore_removed).to_string(), preview.removed)}
+                    {locale}
+                    <div class="restore-actions">
+                        <button
+                            class="apply-restore"
+                            on:click=move |_| {
+                                let backup = backup.clone();
+                                set_portfolios
+                                    .update(|value| {
+                                        backup::restore(
+                                            value,
+                                            backup.portfolios,
+                                            mode.get_untracked(),
+                                        )
+                                    });
+                                if let Some(locale) = backup.locale {
+                                    i18n.set_locale(locale);
+                                }
+                                set_pending.set(None);
+                                set_restored.set(true);
+                            }
+                        >
+                            {t!(i18n, restore_apply)}
+                        </button>
+                        <button on:click=move |_| set_pending.set(None)>{t!(i18n, cancel)}</button>
+                    </div>
+                </section>
+            }
+                .into_any(),
+        )
+    };
+
+    view! {
+        <section class="transfer">
+            <button class="create-backup" on:click=create_backup>
+                <DownloadIcon />
+                {t!(i18n, create_backup)}
+            </button>
+            {restore_button}
+        </section>
+        {restore_preview}
+        <Show when=move || restored.get()>
+            <p class="restore-done">{t!(i18n, restore_done)}</p>
+        </Show>
+    }
+}
+
+/// Saves `content` with the native save dialog on Tauri and as download in the browser.
+pub fn save_file(name: String, extension: &'static str, mime_type: &'static str, content: String) {
+    if tauri::is_tauri() {
+        spawn_local(async move {
+            if let Err(error) = tauri::save_text_file(&name, extension, &content).await {
+                log::error!("{error}");
+            }
+        });
+    } else {
+        download_file(&name, mime_type, &content);
+    }
+}
+
+/// Reads the file selected in a file input and clears the input, so the same file can be
+/// selected again.
+fn read_selected_file(ev: &leptos::ev::Event, on_read: impl FnOnce(String) + 'static) {
+    let input: HtmlInputElement = event_target(ev);
+    let Some(file) = input.files().and_then(|files| files.get(0)) else {
+        return;
+    };
+    input.set_value("");
+    spawn_local(async move {
+        let text = JsFuture::from(file.text())
+            .await
+            .ok()
+            .and_then(|x| x.as_string())
+            .unwrap_or_default();
+        on_read(text);
+    });
+}
+
+/// Offers `content` as file download through a temporary object URL.
+fn download_file(name: &str, mime_type: &str, content: &str) {
+    let parts = web_sys::js_sys::Array::of1(&JsValue::from_str(content));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let Ok(blob) = Blob::new_with_str_sequence_and_options(&parts, &options) else {
+        return;
+    };
+    let Ok(url) = Url::create_object_url_with_blob(&blob) else {
+        return;
+    };
+    if let Some(anchor) = document()
+        .create_element("a")
+        .ok()
+        .and_then(|x| x.dyn_into::<HtmlAnchorElement>().ok())
+    {
+        anchor.set_href(&url);
+        anchor.set_download(name);
+        anchor.click();
+    }
+    let _ = Url::revoke_object_url(&url);
+}
+
+/// Decimal separator of numbers in CSV files, as spreadsheets of the locale write them.
+fn decimal_separator(locale: Locale) -> char {
+    match locale {
+        Locale::de => ',',
+        Locale::en => '.',
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+
+/// Number of steps that can be undone.
+pub const HISTORY_LIMIT: usize = 50;
+/// Changes following each other within this many milliseconds, like typing a number, are undone
+/// together.
+const COALESCE_MS: i64 = 1000;
+
+/// Bounded undo and redo stacks of previous states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoHistory<T> {
+    undo: VecDeque<(usize, T)>,
+    redo: Vec<T>,
+    limit: usize,
+    last_record: Option<i64>,
+    // Number of steps pushed so far, used to identify them
+    steps: usize,
+}
+
+impl<T> Default for UndoHistory<T> {
+    fn default() -> Self {
+        Self::with_limit(HISTORY_LIMIT)
+    }
+}
+
+impl<T> UndoHistory<T> {
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+            limit,
+            last_record: None,
+            steps: 0,
+        }
+    }
+    /// Remembers the state `previous` to a change made at `timestamp`, dropping the oldest step
+    /// once the limit is reached.
+    pub fn record(&mut self, previous: T, timestamp: i64) {
+        self.redo.clear();
+        let is_continued = self
+            .last_record
+            .is_some_and(|x| (timestamp - x).abs() < COALESCE_MS);
+        self.last_record = Some(timestamp);
+        if is_continued && !self.undo.is_empty() {
+            return;
+        }
+        self.push(previous);
+        if self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+    /// Makes the next change its own step, even if it directly follows the last one.
+    pub fn seal(&mut self) {
+        self.last_record = None;
+    }
+    /// Returns the state before the last step, remembering `current` for redo.
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let (_, previous) = self.undo.pop_back()?;
+        self.redo.push(current);
+        self.last_record = None;
+        Some(previous)
+    }
+    /// Undoes the last step only if it is `step`, so later changes are never undone in its place.
+    pub fn undo_step(&mut self, step: usize, current: T) -> Option<T> {
+        if self.last_step() != Some(step) {
+            return None;
+        }
+        self.undo(current)
+    }
+    /// Identifies the step that would be undone next.
+    pub fn last_step(&self) -> Option<usize> {
+        self.undo.back().map(|x| x.0)
+    }
+    /// Returns the state of the last undone step, remembering `current` for undo.
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.push(current);
+        self.last_record = None;
+        Some(next)
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.last_record = None;
+    }
+    fn push(&mut self, previous: T) {
+        self.steps += 1;
+        self.undo.push_back((self.steps, previous));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_history_undoes_and_redoes_steps() {
+        // Arrange
+        let mut sut = UndoHistory::default();
+        sut.record(1, 0);
+        sut.record(2, 5000);
+
+        // Act
+        let undone = sut.undo(3);
+        let redone = sut.redo(2);
+
+        // Assert
+        assert_eq!(undone, Some(2));
+        assert_eq!(redone, Some(3));
+        assert!(sut.can_undo());
+        assert!(!sut.can_redo());
+    }
+
+    #[test]
+    fn undo_history_coalesces_quick_changes_unless_sealed() {
+        // Arrange
+        let mut sut = UndoHistory::default();
+
+        // Act
+        sut.record("", 0);
+        sut.record("1", 300);
+        sut.record("10", 600);
+        sut.seal();
+        sut.record("100", 700);
+
+        // Assert
+        assert_eq!(sut.undo("101"), Some("100"));
+        assert_eq!(sut.undo("100"), Some(""));
+        assert_eq!(sut.undo(""), None);
+    }
+
+    #[test]
+    fn undo_history_undoes_step_only_while_it_is_last() {
+        // Arrange
+        let mut sut = UndoHistory::default();
+        sut.record(1, 0);
+        let step = sut.last_step().unwrap();
+        sut.record(2, 5000);
+
+        // Act
+        let undone_later = sut.undo_step(step, 3);
+        sut.undo(3);
+        let undone = sut.undo_step(step, 2);
+
+        // Assert
+        assert_eq!(undone_later, None);
+        assert_eq!(undone, Some(1));
+        assert!(!sut.can_undo());
+    }
+
+    #[test]
+    fn undo_history_is_bounded_and_new_changes_clear_redo() {
+        // Arrange
+        let mut sut = UndoHistory::with_limit(2);
+        sut.record(1, 0);
+        sut.record(2, 5000);
+        sut.record(3, 10000);
+        sut.undo(4);
+
+        // Act
+        sut.record(3, 15000);
+
+        // Assert
+        assert!(!sut.can_redo());
+        assert_eq!(sut.undo(5), Some(3));
+        assert_eq!(sut.undo(3), Some(2));
+        assert_eq!(sut.undo(2), None);
+    }
+}
@@ -0,0 +1,72 @@
+use codee::string::JsonSerdeCodec;
+use leptos::prelude::*;
+use leptos_use::storage::use_local_storage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const VIEW_SETTINGS_KEY: &str = "view-settings";
+
+/// How the positions of a portfolio are displayed on this device. Kept apart from the portfolio
+/// itself, so changing the view is neither part of the undo history nor of exported portfolios.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewSettings {
+    pub show_units: bool,
+    pub show_currencies: bool,
+    pub show_chart: bool,
+    pub collapsed_groups: Vec<Uuid>,
+}
+
+impl ViewSettings {
+    pub fn is_collapsed(&self, group: Uuid) -> bool {
+        self.collapsed_groups.contains(&group)
+    }
+    pub fn toggle_collapsed(&mut self, group: Uuid) {
+        if self.is_collapsed(group) {
+            self.collapsed_groups.retain(|x| *x != group);
+        } else {
+            self.collapsed_groups.push(group);
+        }
+    }
+}
+
+/// View settings of the portfolio `portfolio_id`, kept in local storage by portfolio.
+pub fn use_view_settings(portfolio_id: Uuid) -> (Signal<ViewSettings>, Callback<ViewSettings>) {
+    let (settings, set_settings, _) =
+        use_local_storage::<HashMap<Uuid, ViewSettings>, JsonSerdeCodec>(VIEW_SETTINGS_KEY);
+    let view = Signal::derive(move || {
+        settings.with(|value| value.get(&portfolio_id).cloned().unwrap_or_default())
+    });
+    let set_view = Callback::new(move |view: ViewSettings| {
+        set_settings.update(|value| {
+            if view == ViewSettings::default() {
+                value.remove(&portfolio_id);
+            } else {
+                value.insert(portfolio_id, view);
+            }
+        });
+    });
+    (view, set_view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_collapsed_collapses_and_expands_group() {
+        // Arrange
+        let mut sut = ViewSettings::default();
+        let group = Uuid::now_v7();
+
+        // Act
+        sut.toggle_collapsed(group);
+        let collapsed = sut.is_collapsed(group);
+        sut.toggle_collapsed(group);
+
+        // Assert
+        assert!(collapsed);
+        assert!(!sut.is_collapsed(group));
+    }
+}
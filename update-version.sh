@@ -17,6 +17,8 @@ update_cargo_toml() {
 
 # Update Cargo.toml files
 update_cargo_toml "Cargo.toml"
+update_cargo_toml "rebalax-core/Cargo.toml"
+update_cargo_toml "rebalax-cli/Cargo.toml"
 update_cargo_toml "src-tauri/Cargo.toml"
 
 # Update tauri.conf.json in src-tauri directory
@@ -24,5 +26,7 @@ sed -i "s/\"version\": \"[^\"]*\"/\"version\": \"$new_version\"/" src-tauri/taur
 
 echo "Version updated to $new_version in:"
 echo "- Cargo.toml"
+echo "- rebalax-core/Cargo.toml"
+echo "- rebalax-cli/Cargo.toml"
 echo "- src-tauri/Cargo.toml"
 echo "- src-tauri/tauri.conf.json"
\ No newline at end of file